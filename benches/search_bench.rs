@@ -1,5 +1,5 @@
-use alice_search::AliceIndex;
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use alice_search::{AliceIndex, IndexBuilder, SamplingStrategy};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 
 fn generate_text(size: usize) -> Vec<u8> {
     let words = [
@@ -95,11 +95,93 @@ fn bench_locate(c: &mut Criterion) {
     });
 }
 
+/// Locate latency vs. space of the SA sampling strategies: the benchmark
+/// id carries the index size, so the report reads as a space/time curve.
+fn bench_locate_sampling(c: &mut Criterion) {
+    let text = generate_text(200_000);
+    let mut group = c.benchmark_group("locate_sampling");
+
+    for sampling in [SamplingStrategy::TextPosition, SamplingStrategy::SaRank] {
+        for step in [4, 16, 64] {
+            let index = IndexBuilder::new()
+                .sampling(sampling)
+                .sample_step(step)
+                .build(&text)
+                .unwrap();
+            let id = BenchmarkId::new(
+                format!("{sampling:?}"),
+                format!("step{step}_{}KiB", index.size_bytes() / 1024),
+            );
+            group.bench_with_input(id, &index, |b, index| {
+                b.iter(|| black_box(index.locate(black_box(b"fox")).sum::<usize>()));
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Dictionary lookups: per-pattern loop vs. the batched, prefetching
+/// queries. The text is a pseudo-random word sequence large enough that
+/// the Wavelet Matrix does not fit in cache, so each step is a miss.
+fn bench_batch(c: &mut Criterion) {
+    let words = generate_text(200);
+    let words: Vec<&[u8]> = words.split_inclusive(|&b| b == b' ').collect();
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    let mut text = Vec::with_capacity(4_000_000);
+    while text.len() < 4_000_000 {
+        text.extend_from_slice(words[next() % words.len()]);
+    }
+    let index = AliceIndex::build(&text, 16);
+
+    let patterns: Vec<&[u8]> = (0..10_000)
+        .map(|_| {
+            let start = next() % (text.len() - 32);
+            &text[start..start + 12 + next() % 20]
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("count_batch");
+    group.throughput(Throughput::Elements(patterns.len() as u64));
+    group.bench_function("loop", |b| {
+        b.iter(|| {
+            let total: usize = patterns.iter().map(|p| index.count(black_box(p))).sum();
+            black_box(total)
+        });
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| black_box(index.count_batch(black_box(&patterns))));
+    });
+    group.finish();
+
+    let patterns = &patterns[..1_000];
+    let mut group = c.benchmark_group("locate_batch");
+    group.throughput(Throughput::Elements(patterns.len() as u64));
+    group.sample_size(20);
+    group.bench_function("loop", |b| {
+        b.iter(|| {
+            let located: Vec<Vec<usize>> = patterns.iter().map(|p| index.locate_all(p)).collect();
+            black_box(located)
+        });
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| black_box(index.locate_batch(black_box(patterns))));
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_build_index,
     bench_count,
     bench_contains,
     bench_locate,
+    bench_locate_sampling,
+    bench_batch,
 );
 criterion_main!(benches);
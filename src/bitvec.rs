@@ -12,7 +12,7 @@ const BLOCK_BITS: usize = 512;
 const WORDS_PER_BLOCK: usize = 8;
 const BLOCK_STRIDE: usize = WORDS_PER_BLOCK + 1; // 1 Header + 8 Body
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct BitVector {
     /// Interleaved data: [Rank0, Word0..7, Rank1, Word8..15, ...]
     data: Vec<u64>,
@@ -58,6 +58,45 @@ impl BitVector {
         self.len += 1;
     }
 
+    /// Build from plain little-endian words (bit `i` = bit `i % 64` of
+    /// `words[i / 64]`), indexed and ready for rank / select.
+    ///
+    /// Produces the same layout as pushing the `len` bits one by one.
+    ///
+    /// # Panics
+    /// Panics if `words` holds fewer than `len` bits.
+    #[must_use]
+    pub fn from_words(words: &[u64], len: usize) -> Self {
+        let word_count = len.div_ceil(64);
+        assert!(
+            words.len() >= word_count,
+            "BitVector::from_words: too few words"
+        );
+        let mut data = Vec::with_capacity(word_count + len.div_ceil(BLOCK_BITS));
+        for block in words[..word_count].chunks(WORDS_PER_BLOCK) {
+            data.push(0);
+            data.extend_from_slice(block);
+        }
+        let mut bv = Self { data, len };
+        bv.build_index();
+        bv
+    }
+
+    /// Overwrite the bit at index `i`.
+    /// `get` sees the change immediately; call `build_index()` again before
+    /// relying on `rank` / `select`.
+    #[inline]
+    pub fn set(&mut self, i: usize, bit: bool) {
+        assert!(i < self.len, "BitVector::set out of bounds");
+        let idx = (i / BLOCK_BITS) * BLOCK_STRIDE + 1 + (i % BLOCK_BITS) / 64;
+        let mask = 1u64 << (i % 64);
+        if bit {
+            self.data[idx] |= mask;
+        } else {
+            self.data[idx] &= !mask;
+        }
+    }
+
     /// Finalize the index. Must be called after all pushes.
     /// Calculates the Rank Headers in-place.
     pub fn build_index(&mut self) {
@@ -158,6 +197,76 @@ impl BitVector {
         r
     }
 
+    /// (Rank1(i), Rank1(j)) for `i <= j`
+    ///
+    /// When both positions fall in the same 512-bit block the header is
+    /// loaded once and the popcount for `j` continues from `i`'s word.
+    /// Otherwise (or if `i > j`) this is two independent `rank1` calls.
+    #[inline(always)]
+    #[must_use]
+    pub fn rank1_pair(&self, i: usize, j: usize) -> (usize, usize) {
+        let (i, j) = (i.min(self.len), j.min(self.len));
+        if i > j || i % BLOCK_BITS == 0 || i / BLOCK_BITS != j / BLOCK_BITS {
+            return (self.rank1(i), self.rank1(j));
+        }
+
+        let base = (i / BLOCK_BITS) * BLOCK_STRIDE;
+        let (word_i, bit_i) = ((i % BLOCK_BITS) / 64, i % 64);
+        let (word_j, bit_j) = ((j % BLOCK_BITS) / 64, j % 64);
+
+        // 1. Shared header load
+        let mut r = self.data[base] as usize;
+
+        // 2. Full words before i's word
+        for w in 0..word_i {
+            r += self.data[base + 1 + w].count_ones() as usize;
+        }
+
+        // 3. Full words between i's word and j's word (continues from i)
+        let mut r_j = r;
+        for w in word_i..word_j {
+            r_j += self.data[base + 1 + w].count_ones() as usize;
+        }
+
+        // 4. Partial words
+        if bit_i > 0 {
+            let mask = (1u64 << bit_i) - 1;
+            r += (self.data[base + 1 + word_i] & mask).count_ones() as usize;
+        }
+        if bit_j > 0 {
+            let mask = (1u64 << bit_j) - 1;
+            r_j += (self.data[base + 1 + word_j] & mask).count_ones() as usize;
+        }
+
+        (r, r_j)
+    }
+
+    /// Hint the CPU to load the block holding bit `i` (no-op off x86_64)
+    ///
+    /// Fetches the header and the word `rank1(i)` ends in, which may sit on
+    /// different cache lines of the 72-byte block.
+    #[inline(always)]
+    pub fn prefetch(&self, i: usize) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+            let base = (i / BLOCK_BITS) * BLOCK_STRIDE;
+            let word = base + 1 + (i % BLOCK_BITS) / 64;
+            if word < self.data.len() {
+                let data = self.data.as_ptr();
+                // SAFETY: both indices are in bounds of `data`; prefetch
+                // only reads and never faults
+                unsafe {
+                    _mm_prefetch::<_MM_HINT_T0>(data.add(base).cast());
+                    _mm_prefetch::<_MM_HINT_T0>(data.add(word).cast());
+                }
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = i;
+    }
+
     /// Rank0(i): Count 0s in [0..i)
     #[inline(always)]
     #[must_use]
@@ -176,6 +285,45 @@ impl BitVector {
         }
     }
 
+    /// Select1(k): Position of the k-th 1 (0-indexed), or `None` if there are
+    /// not that many. Binary search over block headers, then a word scan.
+    #[must_use]
+    pub fn select1(&self, k: usize) -> Option<usize> {
+        if k >= self.rank1(self.len) {
+            return None;
+        }
+        let num_blocks = self.len.div_ceil(BLOCK_BITS);
+
+        // Last block whose cumulative header rank is <= k
+        let mut lo = 0;
+        let mut hi = num_blocks;
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if self.data[mid * BLOCK_STRIDE] as usize <= k {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let base = lo * BLOCK_STRIDE;
+        let mut remaining = k - self.data[base] as usize;
+        for w in 0..WORDS_PER_BLOCK {
+            let word = self.data[base + 1 + w];
+            let ones = word.count_ones() as usize;
+            if remaining < ones {
+                // Clear the lowest `remaining` set bits
+                let mut word = word;
+                for _ in 0..remaining {
+                    word &= word - 1;
+                }
+                return Some(lo * BLOCK_BITS + w * 64 + word.trailing_zeros() as usize);
+            }
+            remaining -= ones;
+        }
+        None
+    }
+
     #[inline]
     #[must_use]
     pub const fn len(&self) -> usize {
@@ -199,6 +347,27 @@ impl Default for BitVector {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rank1_pair_matches_rank1() {
+        for len in [0, 1, 63, 64, 511, 512, 513, 1100, 1536] {
+            let bits: Vec<bool> = (0..len).map(|i| (i * 7 + i / 5) % 3 == 0).collect();
+            let mut bv = BitVector::new();
+            for &b in &bits {
+                bv.push(b);
+            }
+            bv.build_index();
+            for i in (0..=len).step_by(7).chain([len]) {
+                for j in (i..=len).step_by(5).chain([i, len]) {
+                    assert_eq!(
+                        bv.rank1_pair(i, j),
+                        (bv.rank1(i), bv.rank1(j)),
+                        "長さ {len}, 位置 ({i}, {j})"
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_rank1_simple() {
         let mut bv = BitVector::new();
@@ -392,6 +561,59 @@ mod tests {
         assert!(!bv.is_empty());
     }
 
+    #[test]
+    fn test_select1() {
+        let mut bv = BitVector::new();
+        // 3ブロックに跨る疎なビット列
+        for i in 0..1500 {
+            bv.push(i % 7 == 3);
+        }
+        bv.build_index();
+
+        let ones = bv.rank1(1500);
+        for k in 0..ones {
+            let pos = bv.select1(k).unwrap();
+            assert_eq!(pos, k * 7 + 3);
+            assert_eq!(bv.rank1(pos), k);
+        }
+        assert_eq!(bv.select1(ones), None);
+        assert_eq!(BitVector::new().select1(0), None);
+    }
+
+    #[test]
+    fn test_from_words_matches_push() {
+        let bits: Vec<bool> = (0..1100).map(|i| (i * 7 + i / 3) % 5 < 2).collect();
+        let mut pushed = BitVector::new();
+        let mut words = vec![0u64; bits.len().div_ceil(64)];
+        for (i, &bit) in bits.iter().enumerate() {
+            pushed.push(bit);
+            words[i / 64] |= (bit as u64) << (i % 64);
+        }
+        pushed.build_index();
+        let built = BitVector::from_words(&words, bits.len());
+        assert!(built == pushed);
+        assert_eq!(built.rank1(1100), pushed.rank1(1100));
+        assert!(BitVector::from_words(&[], 0) == BitVector::new());
+    }
+
+    #[test]
+    fn test_set_then_rebuild_index() {
+        let mut bv = BitVector::new();
+        for _ in 0..600 {
+            bv.push(false);
+        }
+        bv.build_index();
+        bv.set(3, true);
+        bv.set(550, true);
+        assert!(bv.get(3) && bv.get(550));
+        bv.set(3, false);
+        assert!(!bv.get(3));
+
+        bv.build_index();
+        assert_eq!(bv.rank1(600), 1);
+        assert_eq!(bv.select1(0), Some(550));
+    }
+
     #[test]
     fn test_rank0_complement_of_rank1() {
         let mut bv = BitVector::new();
@@ -0,0 +1,899 @@
+//! Build Configuration, Progress and Cancellation
+//!
+//! [`IndexBuilder`] gathers every build knob (SA / ISA sampling, case
+//! folding, document separator, alphabet compaction, threads, memory
+//! budget), validates them together and records the resulting
+//! [`BuildConfig`] in the index, so an index always knows how it was built.
+//! Instead of a fixed `sample_step`, a [`SampleTuning`] target (index size
+//! or average locate cost) derives the step from the text and the
+//! `size_bytes` model; the choice is reported as a [`SamplingPlan`].
+//! [`SamplingStrategy`] picks which suffix-array entries are sampled.
+//!
+//! A [`BuildObserver`] receives `(phase, fraction)` events from the build
+//! pipeline: every SA-IS recursion level, the BWT pass, SA sampling and
+//! each Wavelet Matrix layer. Returning `ControlFlow::Break` stops the build at
+//! the next checkpoint with [`BuildError::Cancelled`].
+//!
+//! Long loops report at most once per `PROGRESS_INTERVAL` items, so an
+//! observer costs nothing measurable and cancellation stays responsive.
+
+extern crate alloc;
+use alloc::borrow::Cow;
+use core::fmt;
+use core::ops::ControlFlow;
+
+use crate::bwt::SaIndex;
+use crate::fold::{CaseFolding, OffsetMap};
+use crate::intvec::IntVector;
+#[cfg(feature = "normalize")]
+use crate::normalize::Normalization;
+use crate::parallel;
+use crate::search::{self, AliceIndex, BuildStats, IndexOptions};
+
+/// Items processed between two progress checkpoints inside a long loop.
+pub(crate) const PROGRESS_INTERVAL: usize = 1 << 20;
+
+/// ビルドの段階。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildPhase {
+    /// SA-IS の再帰レベル（0 = テキスト自身）。
+    SuffixArray {
+        /// 再帰の深さ
+        level: usize,
+    },
+    /// 接尾辞配列から BWT を取り出す。
+    Bwt,
+    /// Wavelet Matrix の層（上位ビットから `layer` 番目、全 `layers` 層）。
+    WaveletLayer {
+        /// 構築中の層（0 始まり）
+        layer: usize,
+        /// 層の総数
+        layers: usize,
+    },
+    /// SA サンプリング。
+    Sampling,
+}
+
+/// ビルドの進捗を受け取るフック。
+///
+/// `fraction` は段階内の進捗（0.0〜1.0）。`ControlFlow::Break(())` を返すと
+/// ビルドを中断し、`BuildError::Cancelled` を返させる。
+/// クロージャ `FnMut(BuildPhase, f64) -> ControlFlow<()>` も使える。
+pub trait BuildObserver {
+    /// 進捗イベント。
+    fn on_progress(&mut self, phase: BuildPhase, fraction: f64) -> ControlFlow<()>;
+}
+
+impl<F: FnMut(BuildPhase, f64) -> ControlFlow<()>> BuildObserver for F {
+    fn on_progress(&mut self, phase: BuildPhase, fraction: f64) -> ControlFlow<()> {
+        self(phase, fraction)
+    }
+}
+
+/// ビルドのエラー。
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// オブザーバーがビルドを中断した。
+    Cancelled,
+    /// ビルドオプションの組み合わせが不正。
+    InvalidOptions(&'static str),
+    /// ビルドのピークメモリがメモリ予算を超える。
+    MemoryBudgetExceeded {
+        /// 推定ピーク、またはビルド中に予算を超えた時点の確保バイト数
+        required: usize,
+        /// 指定されたメモリ予算
+        budget: usize,
+    },
+    /// SA サンプルを 1 つにしてもインデックスがサイズ目標に収まらない。
+    SizeBudgetTooSmall {
+        /// 達成できる最小のインデックスサイズ
+        minimum: usize,
+        /// 指定されたサイズ目標
+        budget: usize,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cancelled => f.write_str("index build cancelled"),
+            Self::InvalidOptions(reason) => write!(f, "invalid build options: {reason}"),
+            Self::MemoryBudgetExceeded { required, budget } => write!(
+                f,
+                "index build needs about {required} bytes, over the {budget} byte budget"
+            ),
+            Self::SizeBudgetTooSmall { minimum, budget } => write!(
+                f,
+                "index needs at least {minimum} bytes, over the {budget} byte size target"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+/// `sample_step` の自動調整の目標。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SampleTuning {
+    /// インデックス（`size_bytes`）をこのバイト数以内に収める。
+    /// 収まる中で最小（`locate` が最速）のステップを選ぶ。
+    FitBytes(usize),
+    /// 1 出現あたりの平均 LF ステップ数をこれ以下にする
+    /// （[`SamplingPlan::expected_locate_steps`] のモデル）。
+    LocateSteps(usize),
+}
+
+/// どの接尾辞配列エントリをサンプルするか。
+///
+/// | 方式 | マーカー | `locate` の LF ステップ (平均 / 最悪) |
+/// |------|----------|----------------------------------------|
+/// | `TextPosition` | `BitVector` (+N/8 バイト)、LF ごとに rank | `(s-1)/2` / `s-1` |
+/// | `SaRank` | なし（行番号の剰余） | 約 `s-1` / 上限なし |
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SamplingStrategy {
+    /// テキスト位置が `sample_step` の倍数のエントリ。最悪ケースを抑える。
+    #[default]
+    TextPosition,
+    /// SA の行番号が `sample_step` の倍数のエントリ。マーカーのビット列と
+    /// rank が不要で、各 LF ステップの判定が剰余 1 回で済む。
+    SaRank,
+}
+
+impl SamplingStrategy {
+    /// Samples kept for a text of `len` bytes (`len + 1` SA rows)
+    pub(crate) const fn sample_count(self, len: usize, step: usize) -> usize {
+        match self {
+            // Text positions 0, step, ... up to `len` (the sentinel suffix)
+            Self::TextPosition => len / step + 1,
+            // Rows 0, step, ... below `len + 1`
+            Self::SaRank => (len + 1).div_ceil(step),
+        }
+    }
+
+    /// Smallest step keeping at most `samples` (>= 1) samples
+    const fn step_for_samples(self, len: usize, samples: usize) -> usize {
+        match self {
+            Self::TextPosition => len / samples + 1,
+            Self::SaRank => (len + 1).div_ceil(samples),
+        }
+    }
+
+    /// Average LF steps per located occurrence at `step`
+    fn mean_locate_steps(self, step: usize) -> f64 {
+        match self {
+            Self::TextPosition => (step - 1) as f64 / 2.0,
+            // Every LF step lands on a sampled row with probability 1/step
+            Self::SaRank => (step - 1) as f64,
+        }
+    }
+
+    /// Largest step whose average locate walk is at most `steps`
+    const fn step_for_locate_steps(self, steps: usize) -> usize {
+        match self {
+            Self::TextPosition => steps.saturating_mul(2).saturating_add(1),
+            Self::SaRank => steps.saturating_add(1),
+        }
+    }
+}
+
+/// 選ばれた SA サンプリング間隔と、その予測値。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplingPlan {
+    /// 選ばれた `sample_step`
+    pub sample_step: usize,
+    /// サンプリング方式
+    pub sampling: SamplingStrategy,
+    /// 予測インデックスサイズ（`size_bytes()` と同じモデル）
+    pub predicted_bytes: usize,
+}
+
+impl SamplingPlan {
+    /// 1 出現あたりの `locate` の平均 LF ステップ数
+    /// （`TextPosition` は `(sample_step - 1) / 2`、`SaRank` は約 `sample_step - 1`）。
+    #[must_use]
+    pub fn expected_locate_steps(&self) -> f64 {
+        self.sampling.mean_locate_steps(self.sample_step)
+    }
+}
+
+/// インデックスの構築設定。[`IndexBuilder`] が検証して作り、
+/// インデックス自身が保持する（[`AliceIndex::config`]）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BuildConfig {
+    /// SA サンプリング間隔（1 以上）。`tuning` があれば構築時に決まった値。
+    pub sample_step: usize,
+    /// `sample_step` の自動調整の目標。
+    pub tuning: Option<SampleTuning>,
+    /// SA サンプリング方式。
+    pub sampling: SamplingStrategy,
+    /// ISA サンプリング間隔（0 = サンプルなし）。`extract` を速くする。
+    pub isa_sample_step: usize,
+    /// 大文字小文字の折り畳み。テキストとパターンの両方に適用する。
+    pub folding: Option<CaseFolding>,
+    /// Unicode 正規化（NFKC、任意でアクセント除去）。折り畳みの前に、
+    /// テキストとパターンの両方に適用する。
+    #[cfg(feature = "normalize")]
+    pub normalization: Option<Normalization>,
+    /// 文書区切りバイト。これを含むパターンはマッチしない。
+    pub separator: Option<u8>,
+    /// 使用バイトを密なコードに詰めるか。
+    pub compact_alphabet: bool,
+    /// ビルドスレッド数（0 = 全コア）。
+    pub threads: usize,
+    /// ビルド時のメモリ予算（バイト）。
+    pub memory_budget: Option<usize>,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self::from(&IndexOptions::default())
+    }
+}
+
+impl From<&IndexOptions> for BuildConfig {
+    fn from(options: &IndexOptions) -> Self {
+        Self {
+            sample_step: options.sample_step.max(1),
+            tuning: None,
+            sampling: SamplingStrategy::TextPosition,
+            isa_sample_step: 0,
+            folding: None,
+            #[cfg(feature = "normalize")]
+            normalization: None,
+            separator: None,
+            compact_alphabet: options.compact_alphabet,
+            threads: options.threads,
+            memory_budget: None,
+        }
+    }
+}
+
+impl BuildConfig {
+    /// 設定の整合性を検証する。
+    ///
+    /// # Errors
+    /// 不正な組み合わせなら [`BuildError::InvalidOptions`]。
+    pub fn validate(&self) -> Result<(), BuildError> {
+        if self.sample_step == 0 {
+            return Err(BuildError::InvalidOptions("sample_step must be at least 1"));
+        }
+        if self.memory_budget == Some(0) {
+            return Err(BuildError::InvalidOptions("memory_budget must be positive"));
+        }
+        if self.separator == Some(0) {
+            // NUL shares code 0 with the sentinel, which ends `locate`'s LF walk
+            return Err(BuildError::InvalidOptions("separator must not be NUL"));
+        }
+        #[cfg(feature = "normalize")]
+        if self.normalization.is_some() && self.separator.is_some_and(|sep| !sep.is_ascii()) {
+            return Err(BuildError::InvalidOptions(
+                "separator must be ASCII with Unicode normalization",
+            ));
+        }
+        if let (Some(sep), Some(folding)) = (self.separator, self.folding) {
+            if sep.is_ascii_alphabetic() {
+                return Err(BuildError::InvalidOptions(
+                    "separator must not be a letter changed by case folding",
+                ));
+            }
+            if !sep.is_ascii() && folding != CaseFolding::Ascii {
+                return Err(BuildError::InvalidOptions(
+                    "separator must be ASCII with Unicode case folding",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 長さ `text_len`（折り畳み後）のテキストを構築するときの推定ピークメモリ。
+    ///
+    /// 入力テキスト自体は含まない。`memory_budget` の事前判定に使う。
+    /// SA-IS の再帰が深いテキスト（ランダムに近いもの）では縮約後の
+    /// バケット表が大きく、実際のピークがこれを超えることがある。
+    /// その場合はビルド中の確保量で検出する。
+    #[must_use]
+    pub fn estimated_peak_bytes(&self, text_len: usize) -> usize {
+        let n = text_len + 1;
+        let word = core::mem::size_of::<usize>();
+        let entry = if u32::fits(text_len) { 4 } else { word };
+        let blocks = n.div_ceil(512) * 72;
+        let samples = n.div_ceil(self.sample_step.max(1)) * word + blocks;
+        let isa = match self.isa_sample_step {
+            0 => 0,
+            step => n.div_ceil(step) * word,
+        };
+        // Bucket tables of the top SA-IS level (one histogram per thread)
+        let threads = parallel::resolve_threads(self.threads);
+        let buckets = threads.saturating_add(2).saturating_mul(256 * word);
+        // SA + type bits + LMS flags, then SA + BWT + samples,
+        // then BWT + two layer buffers + 8 Wavelet Matrix layers
+        let sais = (entry * n + n.div_ceil(8) + n / 2).saturating_add(buckets);
+        let bwt = entry * n + n + samples + isa;
+        let wavelet = 3 * n + samples + isa + 8 * blocks;
+        sais.max(bwt).max(wavelet)
+    }
+
+    /// Resolve the sample step for the (folded) `text`: the fixed step, or
+    /// the one meeting the tuning target under the `size_bytes` model.
+    pub(crate) fn plan(
+        &self,
+        text: &[u8],
+        offsets: &OffsetMap,
+    ) -> Result<SamplingPlan, BuildError> {
+        let len = text.len();
+        let width = IntVector::width_for(len);
+        let layers = search::modeled_layers(text, self.compact_alphabet);
+        let isa = match self.isa_sample_step {
+            0 => 0,
+            step => len.div_ceil(step),
+        };
+        let extra = IntVector::bytes_for(isa, width) + offsets.size_bytes();
+        let sampling = self.sampling;
+        let size = |step: usize| {
+            let samples = sampling.sample_count(len, step);
+            search::modeled_size(len + 1, layers, sampling, samples, extra)
+        };
+
+        let sample_step = match self.tuning {
+            None => self.sample_step,
+            Some(SampleTuning::LocateSteps(steps)) => sampling.step_for_locate_steps(steps),
+            Some(SampleTuning::FitBytes(budget)) => {
+                let minimum = size(len + 1);
+                if minimum > budget {
+                    return Err(BuildError::SizeBudgetTooSmall { minimum, budget });
+                }
+                // Packed samples the budget leaves room for (at least 1),
+                // then the smallest step keeping at most that many
+                let base = search::modeled_size(len + 1, layers, sampling, 0, extra);
+                let words = (budget - base) / 8;
+                let samples = (words * 64).checked_div(width).unwrap_or(usize::MAX);
+                sampling.step_for_samples(len, samples)
+            }
+        };
+        let sample_step = sample_step.max(1);
+        Ok(SamplingPlan {
+            sample_step,
+            sampling,
+            predicted_bytes: size(sample_step),
+        })
+    }
+
+    /// Apply the configured normalization and case folding to `text` (the
+    /// indexed text or a pattern), with the map back to original offsets.
+    pub(crate) fn transform<'a>(&self, text: &'a [u8]) -> (Cow<'a, [u8]>, OffsetMap) {
+        #[cfg(feature = "normalize")]
+        if let Some(normalization) = self.normalization {
+            let (normalized, offsets) = normalization.normalize_folded(text, self.folding);
+            return (Cow::Owned(normalized), offsets);
+        }
+        match self.folding {
+            Some(folding) => {
+                let (folded, offsets) = folding.fold(text);
+                (Cow::Owned(folded), offsets)
+            }
+            None => (Cow::Borrowed(text), OffsetMap::identity(text.len())),
+        }
+    }
+
+    /// Same index layout: everything except the resource knobs
+    /// (`threads`, `memory_budget`) and the tuning target, which never
+    /// change the output beyond the recorded `sample_step`.
+    pub(crate) fn same_layout(&self, other: &Self) -> bool {
+        #[cfg(feature = "normalize")]
+        if self.normalization != other.normalization {
+            return false;
+        }
+        self.sample_step == other.sample_step
+            && self.sampling == other.sampling
+            && self.isa_sample_step == other.isa_sample_step
+            && self.folding == other.folding
+            && self.separator == other.separator
+            && self.compact_alphabet == other.compact_alphabet
+    }
+}
+
+/// 全ビルドオプションを明示して `AliceIndex` を構築するビルダー。
+///
+/// # Example
+/// ```
+/// use alice_search::{CaseFolding, IndexBuilder};
+///
+/// let index = IndexBuilder::new()
+///     .sample_step(8)
+///     .isa_sample_step(32)
+///     .case_folding(CaseFolding::Simple)
+///     .separator(b'\n')
+///     .threads(2)
+///     .memory_budget(1 << 20)
+///     .build(b"Hello World\nhello there")
+///     .unwrap();
+///
+/// assert_eq!(index.count(b"HELLO"), 2);
+/// assert_eq!(index.count(b"world\nhello"), 0); // 文書を跨がない
+/// assert_eq!(index.config().isa_sample_step, 32);
+/// assert_eq!(index.extract(6..11), b"world");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexBuilder {
+    config: BuildConfig,
+}
+
+impl From<BuildConfig> for IndexBuilder {
+    /// 既存インデックスの設定（`index.config()`）から同じ構成で再構築する。
+    fn from(config: BuildConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl IndexBuilder {
+    /// 既定の設定（`IndexOptions::default()` と同じ）。
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// SA サンプリング間隔（小さいほど `locate` が速く、メモリが増える）。
+    /// 自動調整の目標は解除される。
+    #[must_use]
+    pub const fn sample_step(mut self, step: usize) -> Self {
+        self.config.sample_step = step;
+        self.config.tuning = None;
+        self
+    }
+
+    /// SA サンプリング方式（既定: `TextPosition`）。
+    #[must_use]
+    pub const fn sampling(mut self, sampling: SamplingStrategy) -> Self {
+        self.config.sampling = sampling;
+        self
+    }
+
+    /// インデックスが `bytes` 以内に収まる最小の `sample_step` を選ぶ。
+    #[must_use]
+    pub const fn fit_size(mut self, bytes: usize) -> Self {
+        self.config.tuning = Some(SampleTuning::FitBytes(bytes));
+        self
+    }
+
+    /// `locate` の平均 LF ステップ数が `steps` 以下になる `sample_step` を選ぶ。
+    #[must_use]
+    pub const fn target_locate_steps(mut self, steps: usize) -> Self {
+        self.config.tuning = Some(SampleTuning::LocateSteps(steps));
+        self
+    }
+
+    /// ISA サンプリング間隔（0 = なし）。`extract` は O(step × log σ) になる。
+    #[must_use]
+    pub const fn isa_sample_step(mut self, step: usize) -> Self {
+        self.config.isa_sample_step = step;
+        self
+    }
+
+    /// 大文字小文字の折り畳み。`locate` は元テキストのオフセットを返す。
+    #[must_use]
+    pub const fn case_folding(mut self, folding: CaseFolding) -> Self {
+        self.config.folding = Some(folding);
+        self
+    }
+
+    /// Unicode 正規化（NFKC、任意でアクセント除去）。`case_folding` より先に
+    /// 適用し、`locate` は元テキストのオフセットを返す。
+    #[cfg(feature = "normalize")]
+    #[must_use]
+    pub const fn normalization(mut self, normalization: Normalization) -> Self {
+        self.config.normalization = Some(normalization);
+        self
+    }
+
+    /// 文書区切りバイト。マッチは区切りを跨がない。
+    ///
+    /// NUL (0) は番兵と同じコードになるため使えない（`build` がエラーを返す）。
+    #[must_use]
+    pub const fn separator(mut self, separator: u8) -> Self {
+        self.config.separator = Some(separator);
+        self
+    }
+
+    /// アルファベット圧縮の有無（既定: 有効）。
+    #[must_use]
+    pub const fn compact_alphabet(mut self, compact: bool) -> Self {
+        self.config.compact_alphabet = compact;
+        self
+    }
+
+    /// ビルドスレッド数（0 = 全コア、コア数を超える指定はコア数に丸める）。
+    /// 出力はスレッド数に依存しない。
+    #[must_use]
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = threads;
+        self
+    }
+
+    /// メモリ予算（バイト）。推定ピークが予算を超える場合は構築を始めず、
+    /// 構築中に確保量が予算を超えた場合はその時点で中止する。
+    /// 確保量は大きなバッファを確保サイズで記帳したモデル
+    /// （`BuildStats::peak_bytes` と同じ）で、実測ではない。
+    #[must_use]
+    pub const fn memory_budget(mut self, bytes: usize) -> Self {
+        self.config.memory_budget = Some(bytes);
+        self
+    }
+
+    /// 現在の設定。
+    #[must_use]
+    pub const fn config(&self) -> &BuildConfig {
+        &self.config
+    }
+
+    /// `text` に対して選ばれる `sample_step` と予測サイズ（構築はしない）。
+    ///
+    /// # Errors
+    /// 設定が不正なら [`BuildError::InvalidOptions`]、サイズ目標に
+    /// 収まらないなら [`BuildError::SizeBudgetTooSmall`]。
+    pub fn plan(&self, text: &[u8]) -> Result<SamplingPlan, BuildError> {
+        self.config.validate()?;
+        let (text, offsets) = self.config.transform(text);
+        self.config.plan(&text, &offsets)
+    }
+
+    /// インデックスを構築する。
+    ///
+    /// # Errors
+    /// 設定が不正なら [`BuildError::InvalidOptions`]、推定ピークメモリが
+    /// 予算を超えるなら [`BuildError::MemoryBudgetExceeded`]、サイズ目標に
+    /// 収まらないなら [`BuildError::SizeBudgetTooSmall`]。
+    pub fn build(&self, text: &[u8]) -> Result<AliceIndex, BuildError> {
+        self.build_with_stats(text).map(|(index, _)| index)
+    }
+
+    /// 構築し、ピークメモリと選ばれた `sample_step` の統計も返す。
+    ///
+    /// # Errors
+    /// [`IndexBuilder::build`] と同じ。
+    pub fn build_with_stats(&self, text: &[u8]) -> Result<(AliceIndex, BuildStats), BuildError> {
+        self.config.validate()?;
+        AliceIndex::build_configured(text, &self.config, &mut Progress::none())
+    }
+
+    /// 進捗を `observer` に報告しながら構築する（中断可能）。
+    ///
+    /// # Errors
+    /// [`IndexBuilder::build`] の条件に加え、中断時は [`BuildError::Cancelled`]。
+    pub fn build_observed(
+        &self,
+        text: &[u8],
+        observer: &mut dyn BuildObserver,
+    ) -> Result<AliceIndex, BuildError> {
+        self.config.validate()?;
+        AliceIndex::build_configured(text, &self.config, &mut Progress::new(observer))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Unwrap the result of a build run without an observer.
+pub(crate) fn uncancelled<T>(result: Result<T, BuildError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => unreachable!("{err} without an observer or budget"),
+    }
+}
+
+/// Optional observer threaded through the build pipeline.
+pub(crate) struct Progress<'a> {
+    observer: Option<&'a mut dyn BuildObserver>,
+}
+
+impl<'a> Progress<'a> {
+    /// No observer: every checkpoint passes.
+    pub(crate) const fn none() -> Self {
+        Self { observer: None }
+    }
+
+    pub(crate) fn new(observer: &'a mut dyn BuildObserver) -> Self {
+        Self {
+            observer: Some(observer),
+        }
+    }
+
+    /// Report a checkpoint; `Err(Cancelled)` if the observer asked to stop.
+    #[inline]
+    pub(crate) fn report(&mut self, phase: BuildPhase, fraction: f64) -> Result<(), BuildError> {
+        match &mut self.observer {
+            Some(observer) => match observer.on_progress(phase, fraction.clamp(0.0, 1.0)) {
+                ControlFlow::Continue(()) => Ok(()),
+                ControlFlow::Break(()) => Err(BuildError::Cancelled),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// `report` every `PROGRESS_INTERVAL` items of a loop over `total`.
+    #[inline]
+    pub(crate) fn tick(
+        &mut self,
+        phase: BuildPhase,
+        done: usize,
+        total: usize,
+        range: (f64, f64),
+    ) -> Result<(), BuildError> {
+        if self.observer.is_none() || !done.is_multiple_of(PROGRESS_INTERVAL) {
+            return Ok(());
+        }
+        let (from, to) = range;
+        self.report(
+            phase,
+            from + (to - from) * done as f64 / total.max(1) as f64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_bad_combinations() {
+        let invalid = |builder: IndexBuilder| {
+            matches!(builder.build(b"abc"), Err(BuildError::InvalidOptions(_)))
+        };
+        assert!(invalid(IndexBuilder::new().sample_step(0)));
+        assert!(invalid(IndexBuilder::new().memory_budget(0)));
+        assert!(invalid(IndexBuilder::new().separator(0)));
+        assert!(invalid(
+            IndexBuilder::new()
+                .case_folding(CaseFolding::Ascii)
+                .separator(b'A')
+        ));
+        assert!(invalid(
+            IndexBuilder::new()
+                .case_folding(CaseFolding::Full)
+                .separator(0xFF)
+        ));
+        // 折り畳みがなければ任意のバイトを区切りにできる
+        assert!(IndexBuilder::new().separator(b'A').build(b"abc").is_ok());
+        assert!(IndexBuilder::new()
+            .case_folding(CaseFolding::Ascii)
+            .separator(0xFF)
+            .build(b"abc")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_folding_and_separator_are_recorded_and_applied() {
+        let text = "Straße\nSTRASSE\nstrasse".as_bytes();
+        let index = IndexBuilder::new()
+            .sample_step(2)
+            .case_folding(CaseFolding::Full)
+            .separator(b'\n')
+            .build(text)
+            .unwrap();
+        assert_eq!(index.config().folding, Some(CaseFolding::Full));
+        assert_eq!(index.config().separator, Some(b'\n'));
+        assert_eq!(index.count(b"strasse"), 3);
+        // 位置は元テキストのバイトオフセット
+        let mut hits = index.locate_all("STRASSE".as_bytes());
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 8, 16]);
+        assert_eq!(index.count(b"e\ns"), 0);
+
+        // 記録された設定から同じインデックスを再構築できる
+        let rebuilt = IndexBuilder::from(*index.config()).build(text).unwrap();
+        assert!(rebuilt == index);
+
+        // 大きなサンプル間隔でも区切りで LF の逆走が止まらない
+        let text = b"alpha beta\ngamma alpha\ndelta alpha beta";
+        let index = IndexBuilder::new()
+            .sample_step(64)
+            .separator(b'\n')
+            .build(text)
+            .unwrap();
+        let mut hits = index.locate_all(b"alpha");
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0, 17, 29]);
+        let mut hits = index.locate_all(b"beta");
+        hits.sort_unstable();
+        assert_eq!(hits, vec![6, 35]);
+    }
+
+    #[test]
+    fn test_extract_with_isa_samples() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| b"acgt"[(i * i % 7 % 4) as usize])
+            .collect();
+        for step in [0, 1, 5, 64] {
+            let index = IndexBuilder::new()
+                .isa_sample_step(step)
+                .build(&text)
+                .unwrap();
+            for range in [0..0, 0..10, 17..300, 2990..3000, 1024..1024, 0..3000] {
+                assert_eq!(index.extract(range.clone()), &text[range], "step {step}");
+            }
+            assert_eq!(index.extract_text(), text);
+        }
+    }
+
+    #[test]
+    fn test_plan_predicts_size_bytes() {
+        let english = "The Quick Brown Fox — Straße ".repeat(300);
+        for text in [english.as_bytes(), b"", b"\0\0\0"] {
+            let sparsest = IndexBuilder::new().sample_step(usize::MAX);
+            let floor = sparsest.plan(text).unwrap().predicted_bytes;
+            let builders = [
+                IndexBuilder::new(),
+                IndexBuilder::new().sample_step(3).compact_alphabet(false),
+                IndexBuilder::new()
+                    .isa_sample_step(16)
+                    .case_folding(CaseFolding::Full),
+                IndexBuilder::new().target_locate_steps(5),
+                IndexBuilder::new().fit_size(floor + 800),
+                IndexBuilder::new()
+                    .sampling(SamplingStrategy::SaRank)
+                    .fit_size(floor + 800),
+                IndexBuilder::new()
+                    .sampling(SamplingStrategy::SaRank)
+                    .sample_step(5),
+            ];
+            for builder in builders {
+                let plan = builder.plan(text).unwrap();
+                let (index, stats) = builder.build_with_stats(text).unwrap();
+                assert_eq!(stats.plan, plan);
+                assert_eq!(index.sample_step(), plan.sample_step);
+                assert_eq!(index.size_bytes(), plan.predicted_bytes);
+                assert_eq!(stats.index_bytes, plan.predicted_bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit_size_picks_smallest_fitting_step() {
+        let text = b"to be or not to be, that is the question. ".repeat(500);
+        let sparsest = IndexBuilder::new().sample_step(usize::MAX);
+        let floor = sparsest.plan(&text).unwrap().predicted_bytes;
+        let budgets = [floor, floor + 1_000, floor + 10_000, floor + 200_000];
+        let strategies = [SamplingStrategy::TextPosition, SamplingStrategy::SaRank];
+        for (budget, sampling) in budgets.into_iter().flat_map(|b| strategies.map(|s| (b, s))) {
+            let builder = IndexBuilder::new().sampling(sampling).fit_size(budget);
+            let index = builder.build(&text).unwrap();
+            let step = index.sample_step();
+            assert!(index.size_bytes() <= budget);
+            assert_eq!(index.config().tuning, Some(SampleTuning::FitBytes(budget)));
+            if step > 1 {
+                let denser = builder.sample_step(step - 1).build(&text).unwrap();
+                assert!(
+                    denser.size_bytes() > budget,
+                    "step {step} is not the smallest"
+                );
+            }
+        }
+        // 十分な予算ならすべての位置をサンプルする
+        assert_eq!(
+            IndexBuilder::new()
+                .fit_size(1 << 30)
+                .plan(&text)
+                .unwrap()
+                .sample_step,
+            1
+        );
+
+        assert_eq!(
+            IndexBuilder::new().fit_size(floor - 1).build(&text).err(),
+            Some(BuildError::SizeBudgetTooSmall {
+                minimum: floor,
+                budget: floor - 1
+            })
+        );
+        assert!(IndexBuilder::new().fit_size(floor).build(&text).is_ok());
+    }
+
+    #[test]
+    fn test_target_locate_steps() {
+        let text = b"abracadabra".repeat(100);
+        for steps in [0, 1, 4, 31] {
+            let plan = IndexBuilder::new()
+                .target_locate_steps(steps)
+                .plan(&text)
+                .unwrap();
+            assert_eq!(plan.sample_step, 2 * steps + 1);
+            assert!(plan.expected_locate_steps() <= steps as f64);
+        }
+        // 固定ステップを後から指定すると自動調整は解除される
+        let builder = IndexBuilder::new().target_locate_steps(4).sample_step(2);
+        assert_eq!(builder.config().tuning, None);
+        assert_eq!(builder.build(&text).unwrap().sample_step(), 2);
+    }
+
+    #[test]
+    fn test_rank_sampling_locates_the_same_positions() {
+        let text = b"she sells sea shells by the sea shore; the shells she sells are sea shells"
+            .repeat(40);
+        let patterns: [&[u8]; 5] = [b"sea", b"shells", b"s", b"the sea shore", b"xyz"];
+        for step in [1, 2, 7, 64, 5000] {
+            let by_position = IndexBuilder::new().sample_step(step).build(&text).unwrap();
+            let by_rank = IndexBuilder::new()
+                .sample_step(step)
+                .sampling(SamplingStrategy::SaRank)
+                .build(&text)
+                .unwrap();
+            assert_eq!(by_rank.config().sampling, SamplingStrategy::SaRank);
+            for pattern in patterns {
+                assert_eq!(by_rank.locate_all(pattern), by_position.locate_all(pattern));
+            }
+            // マーカーのビット列がない分だけ小さい
+            if step > 1 {
+                assert!(by_rank.size_bytes() < by_position.size_bytes());
+            }
+        }
+        let plan = IndexBuilder::new()
+            .sampling(SamplingStrategy::SaRank)
+            .target_locate_steps(8)
+            .plan(&text)
+            .unwrap();
+        assert_eq!(plan.sample_step, 9);
+        assert!(plan.expected_locate_steps() <= 8.0);
+    }
+
+    #[test]
+    fn test_memory_budget_estimate_covers_peak() {
+        let mut seed = 7u64;
+        let random: Vec<u8> = (0..40_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                (seed >> 56) as u8
+            })
+            .collect();
+        let english = b"the quick brown fox jumps over the lazy dog ".repeat(900);
+        let runs = vec![b'z'; 40_000];
+        for text in [&english, &runs] {
+            for builder in [
+                IndexBuilder::new(),
+                IndexBuilder::new().sample_step(1).isa_sample_step(4),
+            ] {
+                let (_, stats) = builder.build_with_stats(text).unwrap();
+                let estimate = builder.config().estimated_peak_bytes(text.len());
+                assert!(
+                    stats.peak_bytes <= estimate,
+                    "{} > {estimate}",
+                    stats.peak_bytes
+                );
+                assert!(builder.memory_budget(estimate).build(text).is_ok());
+            }
+        }
+
+        // 巨大なスレッド数でも推定は溢れない（コア数に丸める）
+        let huge = IndexBuilder::new().threads(usize::MAX);
+        assert_eq!(
+            huge.config().estimated_peak_bytes(1000),
+            IndexBuilder::new()
+                .threads(64)
+                .config()
+                .estimated_peak_bytes(1000)
+        );
+        assert!(huge.memory_budget(1 << 30).build(&english).is_ok());
+
+        // 推定が予算を超えれば構築前に拒否する
+        let tight = IndexBuilder::new().memory_budget(random.len());
+        assert!(matches!(
+            tight.build(&random),
+            Err(BuildError::MemoryBudgetExceeded { budget, required })
+                if budget == random.len() && required > budget
+        ));
+        // ランダムなテキストは縮約後のバケット表で推定を超えるが、構築中に検出する
+        let builder = IndexBuilder::new();
+        let (index, stats) = builder.build_with_stats(&random).unwrap();
+        let estimate = builder.config().estimated_peak_bytes(random.len());
+        assert!(stats.peak_bytes > estimate);
+        assert!(matches!(
+            builder.memory_budget(estimate).build(&random),
+            Err(BuildError::MemoryBudgetExceeded { required, .. }) if required > estimate
+        ));
+        let exact = builder
+            .memory_budget(stats.peak_bytes)
+            .build(&random)
+            .unwrap();
+        assert!(exact == index);
+    }
+}
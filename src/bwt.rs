@@ -9,6 +9,9 @@
 extern crate alloc;
 use alloc::vec::Vec;
 
+use crate::build::{BuildError, BuildPhase, Progress};
+use crate::parallel;
+
 /// Sentinel character (lexicographically smallest)
 pub const SENTINEL: u8 = 0;
 
@@ -31,8 +34,167 @@ pub const SENTINEL: u8 = 0;
 //   7. Use the recursively-sorted order to place LMS suffixes accurately.
 //   8. Repeat induced sort (steps 4-5) for the final suffix array.
 //
-// The public entry point `build_suffix_array` converts u8 text to u32 and
-// calls `sais` which operates on a generic integer alphabet.
+// The public entry points wrap the input in a `SaisText` view that yields
+// byte + 1 and a virtual sentinel 0, so the text is never copied, and call
+// `sais`, which is generic over the index type: `u32` halves the suffix
+// array whenever N < 2^32 - 1 (`build_suffix_array_as::<u32>`).
+//
+// With several threads the data-parallel steps (bucket counting,
+// LMS-substring comparison for naming) run on scoped threads. The induced
+// sorts are inherently sequential and stay single-threaded, so the result
+// is identical to the sequential build.
+//
+// Naming and the reduced problem live inside the suffix array itself, so
+// the only scratch is one S/L bit per position, the bucket arrays and one
+// flag per LMS suffix. Every buffer is booked in a `PeakMeter` so builds
+// can report peak memory.
+
+/// Suffix array entry type.
+///
+/// `u32` halves the array for texts shorter than `u32::MAX` bytes; `usize`
+/// works for any length. `MAX` is reserved as the empty-slot marker.
+pub trait SaIndex: Copy + Eq + Send + Sync + 'static {
+    /// Empty-slot marker used during induced sorting.
+    const EMPTY: Self;
+
+    /// Convert from `usize` (the caller guarantees the value fits).
+    fn from_usize(i: usize) -> Self;
+
+    /// Convert to `usize`.
+    fn to_usize(self) -> usize;
+
+    /// Whether a suffix array over `n` text bytes fits this type.
+    #[inline]
+    #[must_use]
+    fn fits(n: usize) -> bool {
+        Self::EMPTY.to_usize() > n
+    }
+}
+
+impl SaIndex for u32 {
+    const EMPTY: Self = Self::MAX;
+
+    #[inline(always)]
+    fn from_usize(i: usize) -> Self {
+        i as Self
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl SaIndex for usize {
+    const EMPTY: Self = Self::MAX;
+
+    #[inline(always)]
+    fn from_usize(i: usize) -> Self {
+        i
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+/// Running / peak byte count of the buffers alive during a build.
+///
+/// A ledger, not an allocator hook: every large buffer is booked at its
+/// allocated size when created and released when dropped; small
+/// allocations and allocator overhead are not seen. `BuildStats::peak_bytes`
+/// and the `memory_budget` check both read this model.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PeakMeter {
+    current: usize,
+    peak: usize,
+    /// Booking past this many bytes fails with `MemoryBudgetExceeded`
+    budget: Option<usize>,
+}
+
+impl PeakMeter {
+    pub(crate) const fn with_budget(budget: Option<usize>) -> Self {
+        Self {
+            current: 0,
+            peak: 0,
+            budget,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn alloc(&mut self, bytes: usize) -> Result<(), BuildError> {
+        self.current += bytes;
+        self.peak = self.peak.max(self.current);
+        match self.budget {
+            Some(budget) if self.current > budget => Err(BuildError::MemoryBudgetExceeded {
+                required: self.current,
+                budget,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn free(&mut self, bytes: usize) {
+        self.current -= bytes;
+    }
+
+    #[inline]
+    pub(crate) const fn peak(&self) -> usize {
+        self.peak
+    }
+}
+
+/// Sentinel-terminated input of SA-IS: `sym(len() - 1)` is 0, every other
+/// symbol is positive.
+pub(crate) trait SaisText: Sync {
+    fn len(&self) -> usize;
+    fn sym(&self, i: usize) -> usize;
+}
+
+/// Bytes shifted to `1..=256` with a virtual sentinel (no copy).
+struct ByteText<'a>(&'a [u8]);
+
+impl SaisText for ByteText<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len() + 1
+    }
+
+    #[inline(always)]
+    fn sym(&self, i: usize) -> usize {
+        self.0.get(i).map_or(0, |&b| b as usize + 1)
+    }
+}
+
+/// Integer symbols in `1..alpha` with a virtual sentinel (no copy).
+struct IntText<'a>(&'a [u32]);
+
+impl SaisText for IntText<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len() + 1
+    }
+
+    #[inline(always)]
+    fn sym(&self, i: usize) -> usize {
+        self.0.get(i).map_or(0, |&c| c as usize)
+    }
+}
+
+/// Reduced string of the recursion (names, sentinel included).
+impl<I: SaIndex> SaisText for [I] {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        <[I]>::len(self)
+    }
+
+    #[inline(always)]
+    fn sym(&self, i: usize) -> usize {
+        self[i].to_usize()
+    }
+}
 
 /// Build Suffix Array using SA-IS (Suffix Array Induced Sorting).
 /// O(N) time and O(N) extra space.
@@ -41,27 +203,96 @@ pub const SENTINEL: u8 = 0;
 /// `text.len()` (the virtual sentinel position).
 #[must_use]
 pub fn build_suffix_array(text: &[u8]) -> Vec<usize> {
-    let n = text.len();
+    build_suffix_array_as(text, 1)
+}
 
-    // Edge cases: empty text or single character.
-    if n == 0 {
-        return vec![0];
-    }
-    if n == 1 {
-        return vec![1, 0];
-    }
+/// [`build_suffix_array`] on `threads` scoped threads (`0` = all cores).
+///
+/// Same output as the sequential build; only the data-parallel SA-IS steps
+/// are split across threads.
+#[must_use]
+pub fn build_suffix_array_parallel(text: &[u8], threads: usize) -> Vec<usize> {
+    build_suffix_array_as(text, threads)
+}
+
+/// Build the suffix array with entries of type `I` (e.g. `u32` for 4 bytes
+/// per entry instead of 8), on `threads` threads (`0` = all cores).
+///
+/// # Panics
+/// Panics if `I` cannot represent `text.len()` (see [`SaIndex::fits`]).
+#[must_use]
+pub fn build_suffix_array_as<I: SaIndex>(text: &[u8], threads: usize) -> Vec<I> {
+    let mut progress = Progress::none();
+    crate::build::uncancelled(build_suffix_array_metered(
+        text,
+        threads,
+        &mut PeakMeter::default(),
+        &mut progress,
+    ))
+}
 
-    // Convert to u32 alphabet, reserving 0 for the appended sentinel.
-    // Original bytes occupy values 1..=256, so alphabet size = 257.
-    let mut s: Vec<u32> = Vec::with_capacity(n + 1);
-    for &b in text {
-        s.push(b as u32 + 1);
+/// [`build_suffix_array_as`] booking its buffers (result included) in
+/// `meter` and reporting each recursion level to `progress`.
+pub(crate) fn build_suffix_array_metered<I: SaIndex>(
+    text: &[u8],
+    threads: usize,
+    meter: &mut PeakMeter,
+    progress: &mut Progress<'_>,
+) -> Result<Vec<I>, BuildError> {
+    assert!(
+        I::fits(text.len()),
+        "text too long for the suffix array index type"
+    );
+    let n = text.len();
+    meter.alloc((n + 1) * core::mem::size_of::<I>())?;
+    let mut sa = vec![I::EMPTY; n + 1];
+    let mut run = SaisRun {
+        threads: parallel::resolve_threads(threads),
+        meter,
+        progress,
+    };
+    // bytes occupy 1..=256, 0 is the sentinel
+    sais(&ByteText(text), &mut sa, 257, 0, &mut run)?;
+    Ok(sa)
+}
+
+/// Build Suffix Array over an integer alphabet using SA-IS.
+///
+/// Every symbol of `text` must lie in `1..alpha`; the value 0 is reserved
+/// for the sentinel, which is appended internally.  The returned array has
+/// length `text.len() + 1` and `SA[0]` is always `text.len()`.
+///
+/// # Panics
+/// Panics if a symbol is 0 or not smaller than `alpha`.
+#[must_use]
+pub fn build_suffix_array_int(text: &[u32], alpha: usize) -> Vec<usize> {
+    for &c in text {
+        assert!(
+            c != 0 && (c as usize) < alpha,
+            "symbol {c} outside 1..{alpha}"
+        );
     }
-    s.push(0); // sentinel — strictly smallest
+    let mut sa = vec![usize::EMPTY; text.len() + 1];
+    let mut run = SaisRun {
+        threads: 1,
+        meter: &mut PeakMeter::default(),
+        progress: &mut Progress::none(),
+    };
+    crate::build::uncancelled(sais(&IntText(text), &mut sa, alpha, 0, &mut run));
+    sa
+}
 
-    let alpha = 257usize; // number of distinct symbols possible
-    let mut sa = vec![0usize; n + 1];
-    sais(&s, &mut sa, alpha);
+/// Build the suffix array of a symbol view computed on the fly (symbols in
+/// `0..alpha`, sentinel included), e.g. the block text of the external
+/// build. The result has length `s.len()`.
+pub(crate) fn build_suffix_array_view<T: SaisText + ?Sized>(s: &T, alpha: usize) -> Vec<usize> {
+    let mut sa = vec![usize::EMPTY; s.len()];
+    let mut run = SaisRun {
+        threads: 1,
+        meter: &mut PeakMeter::default(),
+        progress: &mut Progress::none(),
+    };
+    crate::build::uncancelled(sais(s, &mut sa, alpha, 0, &mut run));
     sa
 }
 
@@ -69,38 +300,72 @@ pub fn build_suffix_array(text: &[u8]) -> Vec<usize> {
 // Core SA-IS implementation
 // ---------------------------------------------------------------------------
 
-/// Classify each position in `s` as S-type (true) or L-type (false).
+/// S/L type of every position, one bit each.
+struct TypeBits(Vec<u64>);
+
+impl TypeBits {
+    #[inline(always)]
+    fn is_s(&self, i: usize) -> bool {
+        (self.0[i / 64] >> (i % 64)) & 1 != 0
+    }
+
+    #[inline(always)]
+    fn set_s(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    /// True if position `i` is an LMS suffix (Left-Most S-type).
+    #[inline(always)]
+    fn is_lms(&self, i: usize) -> bool {
+        i > 0 && self.is_s(i) && !self.is_s(i - 1)
+    }
+
+    const fn bytes(&self) -> usize {
+        self.0.len() * 8
+    }
+}
+
+/// Classify each position in `s` as S-type or L-type.
 /// Position `n` (the sentinel) is always S-type.
-fn classify_sl(s: &[u32]) -> Vec<bool> {
+fn classify_sl<T: SaisText + ?Sized>(s: &T) -> TypeBits {
     let n = s.len();
-    let mut is_s = vec![false; n];
+    let mut types = TypeBits(vec![0; n.div_ceil(64)]);
     // Sentinel is S-type.
-    is_s[n - 1] = true;
-    if n < 2 {
-        return is_s;
-    }
+    types.set_s(n - 1);
     // Scan right to left.
+    let mut next_s = true;
+    let mut next_sym = s.sym(n - 1);
     for i in (0..n - 1).rev() {
-        is_s[i] = match s[i].cmp(&s[i + 1]) {
-            std::cmp::Ordering::Less => true,
-            std::cmp::Ordering::Greater => false,
-            std::cmp::Ordering::Equal => is_s[i + 1], // same character: inherit from right neighbour
+        let sym = s.sym(i);
+        next_s = match sym.cmp(&next_sym) {
+            core::cmp::Ordering::Less => true,
+            core::cmp::Ordering::Greater => false,
+            core::cmp::Ordering::Equal => next_s, // same character: inherit from right neighbour
         };
+        if next_s {
+            types.set_s(i);
+        }
+        next_sym = sym;
     }
-    is_s
-}
-
-/// True if position `i` is an LMS suffix (Left-Most S-type).
-#[inline(always)]
-fn is_lms(is_s: &[bool], i: usize) -> bool {
-    i > 0 && is_s[i] && !is_s[i - 1]
+    types
 }
 
 /// Compute bucket sizes (frequencies) for each symbol.
-fn bucket_sizes(s: &[u32], alpha: usize) -> Vec<usize> {
-    let mut bkt = vec![0usize; alpha];
-    for &c in s {
-        bkt[c as usize] += 1;
+/// With several threads, per-chunk histograms are summed.
+fn bucket_sizes<T: SaisText + ?Sized>(s: &T, alpha: usize, threads: usize) -> Vec<usize> {
+    let ranges = parallel::chunk_ranges(s.len(), threads, 1);
+    let mut partial = parallel::scope_map(ranges, |r| {
+        let mut bkt = vec![0usize; alpha];
+        for i in r {
+            bkt[s.sym(i)] += 1;
+        }
+        bkt
+    });
+    let mut bkt = partial.swap_remove(0);
+    for other in partial {
+        for (total, count) in bkt.iter_mut().zip(other) {
+            *total += count;
+        }
     }
     bkt
 }
@@ -122,71 +387,85 @@ fn bucket_tails(bkt: &[usize]) -> Vec<usize> {
     let mut sum = 0;
     for (i, &b) in bkt.iter().enumerate() {
         sum += b;
-        tail[i] = sum - 1;
+        tail[i] = sum.wrapping_sub(1);
     }
     tail
 }
 
 /// Step 3 — scatter LMS suffixes into the tails of their buckets.
-fn place_lms(s: &[u32], sa: &mut [usize], tail: &mut [usize], is_s: &[bool]) {
-    // Sentinel marker: usize::MAX means "empty".
-    sa.fill(usize::MAX);
+fn place_lms<T: SaisText + ?Sized, I: SaIndex>(
+    s: &T,
+    sa: &mut [I],
+    tail: &mut [usize],
+    types: &TypeBits,
+) {
+    sa.fill(I::EMPTY);
     for i in (0..s.len()).rev() {
-        if is_lms(is_s, i) {
-            let c = s[i] as usize;
-            sa[tail[c]] = i;
-            // Saturating sub so we don't wrap on 0 (though bucket won't be empty here).
+        if types.is_lms(i) {
+            let c = s.sym(i);
+            sa[tail[c]] = I::from_usize(i);
+            // Wrapping sub so we don't panic on 0 (though bucket won't be empty here).
             tail[c] = tail[c].wrapping_sub(1);
         }
     }
 }
 
 /// Step 4 — induced-sort L-type suffixes left-to-right.
-fn induce_l(s: &[u32], sa: &mut [usize], head: &mut [usize], is_s: &[bool]) {
-    let n = s.len();
-    for i in 0..n {
-        if sa[i] == usize::MAX {
-            continue;
-        }
+/// `tick(i)` is the progress checkpoint of the scan.
+fn induce_l<T: SaisText + ?Sized, I: SaIndex>(
+    s: &T,
+    sa: &mut [I],
+    head: &mut [usize],
+    types: &TypeBits,
+    tick: &mut dyn FnMut(usize) -> Result<(), BuildError>,
+) -> Result<(), BuildError> {
+    for i in 0..sa.len() {
+        tick(i)?;
         let j = sa[i];
-        if j == 0 {
+        if j == I::EMPTY || j.to_usize() == 0 {
             continue;
         }
-        let p = j - 1;
-        if !is_s[p] {
+        let p = j.to_usize() - 1;
+        if !types.is_s(p) {
             // p is L-type
-            let c = s[p] as usize;
-            sa[head[c]] = p;
+            let c = s.sym(p);
+            sa[head[c]] = I::from_usize(p);
             head[c] += 1;
         }
     }
+    Ok(())
 }
 
 /// Step 5 — induced-sort S-type suffixes right-to-left.
-fn induce_s(s: &[u32], sa: &mut [usize], tail: &mut [usize], is_s: &[bool]) {
-    let n = s.len();
-    for i in (0..n).rev() {
-        if sa[i] == usize::MAX {
-            continue;
-        }
+/// `tick(k)` is the progress checkpoint after `k` slots.
+fn induce_s<T: SaisText + ?Sized, I: SaIndex>(
+    s: &T,
+    sa: &mut [I],
+    tail: &mut [usize],
+    types: &TypeBits,
+    tick: &mut dyn FnMut(usize) -> Result<(), BuildError>,
+) -> Result<(), BuildError> {
+    for i in (0..sa.len()).rev() {
+        tick(sa.len() - 1 - i)?;
         let j = sa[i];
-        if j == 0 {
+        if j == I::EMPTY || j.to_usize() == 0 {
             continue;
         }
-        let p = j - 1;
-        if is_s[p] {
+        let p = j.to_usize() - 1;
+        if types.is_s(p) {
             // p is S-type
-            let c = s[p] as usize;
-            sa[tail[c]] = p;
+            let c = s.sym(p);
+            sa[tail[c]] = I::from_usize(p);
             tail[c] = tail[c].wrapping_sub(1);
         }
     }
+    Ok(())
 }
 
 /// Check whether two LMS substrings (starting at `i` and `j` in `s`) are equal.
 /// An LMS substring runs from an LMS position up to and including the *next* LMS
 /// position (inclusive).
-fn lms_substrings_equal(s: &[u32], is_s: &[bool], i: usize, j: usize) -> bool {
+fn lms_substrings_equal<T: SaisText + ?Sized>(s: &T, types: &TypeBits, i: usize, j: usize) -> bool {
     // Both must be LMS (caller ensures this for i==j case).
     let n = s.len();
     let mut k = 0usize;
@@ -194,13 +473,13 @@ fn lms_substrings_equal(s: &[u32], is_s: &[bool], i: usize, j: usize) -> bool {
         let ai = i + k < n;
         let aj = j + k < n;
         // Compare characters.
-        let ci = if ai { s[i + k] } else { u32::MAX };
-        let cj = if aj { s[j + k] } else { u32::MAX };
+        let ci = if ai { s.sym(i + k) } else { usize::MAX };
+        let cj = if aj { s.sym(j + k) } else { usize::MAX };
         if ci != cj {
             return false;
         }
         // After the first position check, see if both reached the next LMS boundary.
-        if k > 0 && is_lms(is_s, i + k) && is_lms(is_s, j + k) {
+        if k > 0 && types.is_lms(i + k) && types.is_lms(j + k) {
             return true;
         }
         // If one side ended (hit sentinel) but not both, they differ.
@@ -211,146 +490,209 @@ fn lms_substrings_equal(s: &[u32], is_s: &[bool], i: usize, j: usize) -> bool {
     }
 }
 
+/// Bytes of a `Vec<T>` with `len` elements, for the `PeakMeter`.
+#[inline]
+const fn vec_bytes<T>(len: usize) -> usize {
+    len * core::mem::size_of::<T>()
+}
+
+/// Build-wide state shared by every SA-IS level.
+struct SaisRun<'a, 'p> {
+    /// `threads` > 1 parallelises bucket counting and LMS naming
+    threads: usize,
+    meter: &'a mut PeakMeter,
+    progress: &'a mut Progress<'p>,
+}
+
+impl SaisRun<'_, '_> {
+    /// Checkpoint `done` of an induced-sort scan over `n` slots, mapped to
+    /// `range` of the level's progress.
+    fn tick(
+        &mut self,
+        level: usize,
+        done: usize,
+        n: usize,
+        range: (f64, f64),
+    ) -> Result<(), BuildError> {
+        self.progress
+            .tick(BuildPhase::SuffixArray { level }, done, n, range)
+    }
+}
+
 /// Recursive SA-IS on string `s` with alphabet size `alpha`.
 /// Result is written into `sa` which must have length `s.len()`.
-fn sais(s: &[u32], sa: &mut [usize], alpha: usize) {
+/// `level` is the recursion depth, reported to the progress observer.
+fn sais<T: SaisText + ?Sized, I: SaIndex>(
+    s: &T,
+    sa: &mut [I],
+    alpha: usize,
+    level: usize,
+    run: &mut SaisRun<'_, '_>,
+) -> Result<(), BuildError> {
     let n = s.len();
+    let phase = BuildPhase::SuffixArray { level };
+    run.progress.report(phase, 0.0)?;
+    let threads = run.threads;
 
     // ---- Base case: n == 1 (only the sentinel) ----
     if n == 1 {
-        sa[0] = 0;
-        return;
+        sa[0] = I::from_usize(0);
+        return run.progress.report(phase, 1.0);
     }
 
     // ---- Base case: n == 2 ----
     // s = [x, sentinel], sentinel < x always, so SA = [1, 0].
     if n == 2 {
-        sa[0] = 1;
-        sa[1] = 0;
-        return;
+        sa[0] = I::from_usize(1);
+        sa[1] = I::from_usize(0);
+        return run.progress.report(phase, 1.0);
     }
 
     // 1. Classify S / L.
-    let is_s = classify_sl(s);
-
-    // 2. Bucket information.
-    let bkt = bucket_sizes(s, alpha);
+    let types = classify_sl(s);
+    run.meter.alloc(types.bytes())?;
+
+    // 2. Bucket information (plus one head / tail array alive at a time).
+    // Booked before counting: reduced levels can have large alphabets.
+    // One histogram per chunk `bucket_sizes` actually runs.
+    let bucket_bytes = vec_bytes::<usize>(alpha);
+    let histograms = parallel::chunk_ranges(n, threads, 1).len();
+    run.meter.alloc(bucket_bytes * histograms)?;
+    let bkt = bucket_sizes(s, alpha, threads);
+    run.meter.free(bucket_bytes * histograms);
+    run.meter.alloc(2 * bucket_bytes)?;
 
     // 3. Place LMS suffixes at bucket tails.
-    {
-        let mut tail = bucket_tails(&bkt);
-        place_lms(s, sa, &mut tail, &is_s);
-    }
+    place_lms(s, sa, &mut bucket_tails(&bkt), &types);
 
     // 4. Induced-sort L-type.
-    {
-        let mut head = bucket_heads(&bkt);
-        induce_l(s, sa, &mut head, &is_s);
-    }
+    induce_l(s, sa, &mut bucket_heads(&bkt), &types, &mut |done| {
+        run.tick(level, done, n, (0.0, 0.2))
+    })?;
 
     // 5. Induced-sort S-type.
-    {
-        let mut tail = bucket_tails(&bkt);
-        induce_s(s, sa, &mut tail, &is_s);
-    }
+    induce_s(s, sa, &mut bucket_tails(&bkt), &types, &mut |done| {
+        run.tick(level, done, n, (0.2, 0.4))
+    })?;
 
     // 6. Collect sorted LMS positions and assign compact names.
     //
     // After the two induced sorts, the LMS suffixes appear in sorted order
-    // within `sa`. We read them off, name them by comparing adjacent LMS
-    // substrings, and build a reduced string `s1` of length = #LMS suffixes.
-
-    // Collect LMS positions in sorted order.
-    let lms_sorted: Vec<usize> = sa
-        .iter()
-        .filter(|&&x| x != usize::MAX && is_lms(&is_s, x))
-        .copied()
-        .collect();
-
-    // Assign names: equal consecutive LMS substrings get the same name.
-    let _num_lms = lms_sorted.len();
-
-    // name_of[i] = compact integer name for the LMS suffix at original position i.
-    // We reuse part of `sa` as scratch to avoid extra allocation.
-    // Specifically: we need n slots for name_of; we allocate separately
-    // (the overall algorithm is still O(N) total allocations).
-    let mut name_of = vec![0u32; n];
-    let mut current_name = 0u32;
-    let mut prev_lms: Option<usize> = None;
-    for &pos in &lms_sorted {
-        let new_name = match prev_lms {
-            None => {
-                current_name // first LMS always gets name 0
-            }
-            Some(prev) => {
-                if !lms_substrings_equal(s, &is_s, prev, pos) {
-                    current_name += 1;
-                }
-                current_name
-            }
-        };
-        name_of[pos] = new_name;
-        prev_lms = Some(pos);
+    // within `sa`. Everything below works inside `sa` itself (no scratch
+    // arrays besides one flag per LMS suffix):
+    //   sa[..n1]          sorted LMS positions, later the reduced SA
+    //   sa[n1..]          name of the LMS suffix at position i in slot n1 + i/2
+    //                     (LMS positions are never adjacent: no collisions)
+    //   sa[n - n1..]      reduced string s1 in text order, later the LMS
+    //                     positions in text order
+    let mut n1 = 0;
+    for i in 0..n {
+        let x = sa[i];
+        if x != I::EMPTY && types.is_lms(x.to_usize()) {
+            sa[n1] = x;
+            n1 += 1;
+        }
     }
-    let alpha1 = (current_name + 1) as usize; // new alphabet size
 
-    // Build reduced string s1: LMS positions in *text order* (left to right),
-    // values = their compact names.
-    let lms_positions_textorder: Vec<usize> = (0..n).filter(|&i| is_lms(&is_s, i)).collect();
-    // lms_positions_textorder is already in ascending order.
-
-    let s1: Vec<u32> = lms_positions_textorder
-        .iter()
-        .map(|&i| name_of[i])
-        .collect();
-    let n1 = s1.len(); // == num_lms
+    // differs[k]: LMS substring k differs from k - 1 (independent comparisons)
+    let (lms_sorted, names) = sa.split_at_mut(n1);
+    let ranges = parallel::chunk_ranges(n1, threads, 1);
+    let differs = parallel::scope_map(ranges, |r| {
+        r.map(|k| {
+            k > 0
+                && !lms_substrings_equal(
+                    s,
+                    &types,
+                    lms_sorted[k - 1].to_usize(),
+                    lms_sorted[k].to_usize(),
+                )
+        })
+        .collect::<Vec<bool>>()
+    });
+    run.meter.alloc(n1)?;
+
+    names.fill(I::EMPTY);
+    let mut current_name = 0usize;
+    for (&pos, differs) in lms_sorted.iter().zip(differs.iter().flatten()) {
+        // first LMS always gets name 0
+        if *differs {
+            current_name += 1;
+        }
+        names[pos.to_usize() / 2] = I::from_usize(current_name);
+    }
+    let alpha1 = current_name + 1; // new alphabet size
+    run.progress.report(phase, 0.5)?;
+    drop(differs);
+    run.meter.free(n1);
+
+    // Gather names in text order into the tail: s1 = sa[n - n1..].
+    let mut write = n;
+    for i in (n1..n).rev() {
+        if sa[i] != I::EMPTY {
+            write -= 1;
+            sa[write] = sa[i];
+        }
+    }
 
     // 7. Sort reduced problem — recurse only if names are not yet unique.
-    let mut sa1 = vec![0usize; n1];
-    if alpha1 < n1 {
-        // Not all unique: recurse.
-        sais(&s1, &mut sa1, alpha1);
-    } else {
-        // All names unique: directly invert the name array (counting sort).
-        // s1[i] is unique for each i, so sa1[s1[i]] = i.
-        for (i, &name) in s1.iter().enumerate() {
-            sa1[name as usize] = i;
+    {
+        let (sa1, rest) = sa.split_at_mut(n1);
+        let s1 = &rest[rest.len() - n1..];
+        if alpha1 < n1 {
+            // Not all unique: recurse.
+            sais(s1, sa1, alpha1, level + 1, run)?;
+        } else {
+            // All names unique: directly invert the name array (counting sort).
+            // s1[i] is unique for each i, so sa1[s1[i]] = i.
+            for (i, &name) in s1.iter().enumerate() {
+                sa1[name.to_usize()] = I::from_usize(i);
+            }
         }
     }
 
-    // sa1 now gives the sorted order of *indices into lms_positions_textorder*.
-    // Convert back to original positions.
-    let lms_sorted_final: Vec<usize> = sa1
-        .iter()
-        .map(|&idx| lms_positions_textorder[idx])
-        .collect();
+    // sa1 gives the sorted order of *indices into the text-order LMS list*.
+    // Store that list over s1, then convert back to original positions.
+    let mut write = n - n1;
+    for i in 0..n {
+        if types.is_lms(i) {
+            sa[write] = I::from_usize(i);
+            write += 1;
+        }
+    }
+    for i in 0..n1 {
+        sa[i] = sa[n - n1 + sa[i].to_usize()];
+    }
 
     // 8. Final induced sort using accurately ordered LMS suffixes.
 
     // Re-place LMS suffixes in bucket tails using the correct order.
+    // Insert in *reverse* of sorted order so that earlier (smaller) LMS
+    // suffixes end up at lower tail positions within their bucket; the
+    // tail slot of the i-th smallest LMS suffix is never below i.
     {
         let mut tail = bucket_tails(&bkt);
-        sa.fill(usize::MAX);
-        // Insert in *reverse* of sorted order so that earlier (smaller) LMS
-        // suffixes end up at lower tail positions within their bucket.
-        for &pos in lms_sorted_final.iter().rev() {
-            let c = s[pos] as usize;
+        sa[n1..].fill(I::EMPTY);
+        for i in (0..n1).rev() {
+            let pos = sa[i];
+            sa[i] = I::EMPTY;
+            let c = s.sym(pos.to_usize());
             sa[tail[c]] = pos;
             tail[c] = tail[c].wrapping_sub(1);
         }
     }
 
     // Induced-sort L.
-    {
-        let mut head = bucket_heads(&bkt);
-        induce_l(s, sa, &mut head, &is_s);
-    }
+    induce_l(s, sa, &mut bucket_heads(&bkt), &types, &mut |done| {
+        run.tick(level, done, n, (0.5, 0.75))
+    })?;
 
     // Induced-sort S.
-    {
-        let mut tail = bucket_tails(&bkt);
-        induce_s(s, sa, &mut tail, &is_s);
-    }
+    induce_s(s, sa, &mut bucket_tails(&bkt), &types, &mut |done| {
+        run.tick(level, done, n, (0.75, 1.0))
+    })?;
+
+    run.meter.free(2 * bucket_bytes + types.bytes());
+    run.progress.report(phase, 1.0)
 }
 
 // ---------------------------------------------------------------------------
@@ -422,6 +764,47 @@ mod tests {
     // SA-IS correctness: matches naive for known strings
     // ------------------------------------------------------------------
 
+    #[test]
+    fn test_parallel_suffix_array_matches_sequential() {
+        let long: Vec<u8> = (0..3000u32)
+            .map(|i| b"ab"[(i % 7 == 0) as usize] ^ (i % 5) as u8)
+            .collect();
+        for text in [&b"mississippi"[..], b"aaaaaaaaaaaaaaaa", b"xy", &long] {
+            for threads in [2, 4, 7] {
+                assert_eq!(
+                    build_suffix_array_parallel(text, threads),
+                    build_suffix_array(text)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_u32_suffix_array_matches_usize() {
+        for text in [&b""[..], b"a", b"banana", b"abracadabra\xff\x00abra"] {
+            let wide = build_suffix_array(text);
+            let narrow: Vec<u32> = build_suffix_array_as(text, 1);
+            assert!(wide.iter().zip(&narrow).all(|(&w, &n)| w == n as usize));
+            assert_eq!(wide.len(), narrow.len());
+        }
+        assert!(u32::fits(1 << 20));
+        assert!(!u32::fits(u32::MAX as usize));
+    }
+
+    #[test]
+    fn test_peak_meter_counts_sa_and_scratch() {
+        let text: Vec<u8> = (0..10_000u32)
+            .map(|i| ((i % 251) ^ (i / 7)) as u8)
+            .collect();
+        let mut meter = PeakMeter::default();
+        let sa: Vec<u32> =
+            build_suffix_array_metered(&text, 1, &mut meter, &mut Progress::none()).unwrap();
+        let sa_bytes = sa.len() * 4;
+        assert!(meter.peak() > sa_bytes);
+        // 作業領域（型ビット・バケット）は SA 本体より小さい
+        assert!(meter.peak() < 5 * sa_bytes / 2, "peak = {}", meter.peak());
+    }
+
     #[test]
     fn test_suffix_array_banana() {
         let text = b"banana";
@@ -746,6 +1129,27 @@ mod tests {
         assert_eq!(build_suffix_array(&text), naive_suffix_array(&text));
     }
 
+    #[test]
+    fn test_int_alphabet_matches_byte_alphabet() {
+        // バイト列を +1 した整数列の SA はバイト版と一致する
+        let text = b"mississippi";
+        let ints: Vec<u32> = text.iter().map(|&b| b as u32 + 1).collect();
+        assert_eq!(build_suffix_array_int(&ints, 257), build_suffix_array(text));
+    }
+
+    #[test]
+    fn test_int_alphabet_large_symbols() {
+        // 256 を超える記号値でも正しくソートされる
+        let text: Vec<u32> = vec![1000, 3, 1000, 3, 70_000, 1];
+        let sa = build_suffix_array_int(&text, 70_001);
+        assert_eq!(sa.len(), text.len() + 1);
+        for i in 1..sa.len() {
+            let a = &text[sa[i - 1]..];
+            let b = &text[sa[i]..];
+            assert!(a < b, "SA not sorted at {i}");
+        }
+    }
+
     #[test]
     fn test_sa_first_entry_is_sentinel_position() {
         // SA[0] は常に text.len()（仮想センチネルの位置）でなければならない。
@@ -0,0 +1,256 @@
+//! DNA Sequence Index (Reverse-Complement Aware)
+//!
+//! **Architecture**:
+//! - Sequences are normalized to `ACGTN` (lowercase folded, IUPAC codes → `N`)
+//! - Alphabet compaction gives σ = 5 (+ sentinel) → 3 Wavelet Matrix layers
+//! - Reverse strand: backward search for `revcomp(pattern)` on the same index
+//!
+//! No second index over `revcomp(text)` is needed: a reverse-strand hit of
+//! `P` at position `p` is exactly a forward hit of `revcomp(P)` at `p`.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::search::{AliceIndex, LocateIter};
+
+/// マッチしたストランド（鎖）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strand {
+    /// 順鎖（パターンそのものが一致）。
+    Forward,
+    /// 逆鎖（パターンの逆相補配列が一致）。
+    Reverse,
+}
+
+/// 塩基を `ACGTN` に正規化する。小文字は大文字に、それ以外は `N`。
+#[inline]
+#[must_use]
+pub const fn normalize_base(b: u8) -> u8 {
+    match b {
+        b'A' | b'a' => b'A',
+        b'C' | b'c' => b'C',
+        b'G' | b'g' => b'G',
+        b'T' | b't' | b'U' | b'u' => b'T',
+        _ => b'N',
+    }
+}
+
+/// 正規化済み塩基の相補塩基。
+#[inline]
+#[must_use]
+pub const fn complement(b: u8) -> u8 {
+    match b {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        _ => b'N',
+    }
+}
+
+/// 逆相補配列を返す（入力は正規化される）。
+#[must_use]
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|&b| complement(normalize_base(b)))
+        .collect()
+}
+
+/// 逆相補を考慮した DNA 配列インデックス。
+///
+/// `count` / `locate` は両鎖のマッチを返す。逆鎖の位置は順鎖座標での
+/// マッチ開始位置。逆相補が自身と等しい (回文) パターンは両鎖で報告される。
+///
+/// 空パターンはどちらの鎖にもマッチしない（`count` は 0）。全位置に
+/// マッチする `AliceIndex::count(b"")` とは異なる。
+pub struct DnaIndex {
+    inner: AliceIndex,
+}
+
+impl DnaIndex {
+    /// 配列からインデックスを構築する。
+    #[must_use]
+    pub fn build(seq: &[u8], sample_step: usize) -> Self {
+        let normalized: Vec<u8> = seq.iter().map(|&b| normalize_base(b)).collect();
+        Self {
+            inner: AliceIndex::build(&normalized, sample_step),
+        }
+    }
+
+    /// 両鎖の出現回数（空パターンは 0）。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.count_strand(pattern, Strand::Forward) + self.count_strand(pattern, Strand::Reverse)
+    }
+
+    /// 指定した鎖の出現回数。
+    #[must_use]
+    pub fn count_strand(&self, pattern: &[u8], strand: Strand) -> usize {
+        let range = self.strand_range(pattern, strand);
+        range.end - range.start
+    }
+
+    /// いずれかの鎖に存在するか。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.count(pattern) > 0
+    }
+
+    /// 両鎖の出現位置を `(位置, 鎖)` で返すイテレータ。順鎖 → 逆鎖の順。
+    #[must_use]
+    pub fn locate(&self, pattern: &[u8]) -> DnaLocateIter<'_> {
+        DnaLocateIter {
+            forward: self
+                .inner
+                .locate_range(self.strand_range(pattern, Strand::Forward)),
+            reverse: self
+                .inner
+                .locate_range(self.strand_range(pattern, Strand::Reverse)),
+        }
+    }
+
+    /// 全出現位置を Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<(usize, Strand)> {
+        self.locate(pattern).collect()
+    }
+
+    /// 配列長。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.inner.text_len()
+    }
+
+    /// インデックスの概算バイトサイズ。
+    #[must_use]
+    pub const fn size_bytes(&self) -> usize {
+        self.inner.size_bytes()
+    }
+
+    /// 内部の `AliceIndex`（正規化済み配列のインデックス）。
+    #[must_use]
+    pub const fn inner(&self) -> &AliceIndex {
+        &self.inner
+    }
+
+    /// SA range of `pattern` on `strand`, normalized on the fly.
+    ///
+    /// Backward search reads the query last-to-first; for the reverse strand
+    /// that is `revcomp(pattern)` read backwards, i.e. the complement of
+    /// `pattern` read forwards, so no query buffer is built.
+    fn strand_range(&self, pattern: &[u8], strand: Strand) -> core::ops::Range<usize> {
+        if pattern.is_empty() {
+            return 0..0;
+        }
+        match strand {
+            Strand::Forward => self
+                .inner
+                .backward_search_rev(pattern.iter().rev().map(|&b| normalize_base(b))),
+            Strand::Reverse => self
+                .inner
+                .backward_search_rev(pattern.iter().map(|&b| complement(normalize_base(b)))),
+        }
+    }
+}
+
+/// Iterator over `(position, strand)` results.
+pub struct DnaLocateIter<'a> {
+    forward: LocateIter<'a>,
+    reverse: LocateIter<'a>,
+}
+
+impl Iterator for DnaLocateIter<'_> {
+    type Item = (usize, Strand);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pos) = self.forward.next() {
+            return Some((pos, Strand::Forward));
+        }
+        self.reverse.next().map(|pos| (pos, Strand::Reverse))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.forward.len() + self.reverse.len();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for DnaLocateIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ACGTN"), b"NACGT");
+        assert_eq!(reverse_complement(b"aacg"), b"CGTT");
+        assert_eq!(reverse_complement(b""), b"");
+    }
+
+    #[test]
+    fn test_both_strands() {
+        // "AAC" の逆相補は "GTT"
+        let index = DnaIndex::build(b"AACGGTTAAC", 1);
+
+        assert_eq!(index.count_strand(b"AAC", Strand::Forward), 2);
+        assert_eq!(index.count_strand(b"AAC", Strand::Reverse), 1);
+        assert_eq!(index.count(b"AAC"), 3);
+
+        let mut hits = index.locate_all(b"AAC");
+        hits.sort_unstable_by_key(|&(p, _)| p);
+        assert_eq!(
+            hits,
+            vec![
+                (0, Strand::Forward),
+                (4, Strand::Reverse),
+                (7, Strand::Forward)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_palindromic_pattern_reported_on_both_strands() {
+        // "ACGT" は自身の逆相補
+        let index = DnaIndex::build(b"TTACGTTT", 2);
+        assert_eq!(index.count_strand(b"ACGT", Strand::Forward), 1);
+        assert_eq!(index.count_strand(b"ACGT", Strand::Reverse), 1);
+        assert_eq!(index.locate(b"ACGT").len(), 2);
+    }
+
+    #[test]
+    fn test_normalization() {
+        // 小文字と IUPAC コードは正規化される
+        let index = DnaIndex::build(b"acgtRYacgt", 1);
+        assert_eq!(index.count_strand(b"ACGT", Strand::Forward), 2);
+        assert_eq!(index.count_strand(b"acgt", Strand::Forward), 2);
+        assert_eq!(index.count_strand(b"NN", Strand::Forward), 1);
+        assert_eq!(index.count_strand(b"tn", Strand::Forward), 1);
+        assert_eq!(index.text_len(), 10);
+    }
+
+    #[test]
+    fn test_compact_alphabet_layers() {
+        let index = DnaIndex::build(b"ACGTNACGT", 4);
+        assert_eq!(index.inner().alphabet().sigma(), 5);
+        assert!(index.size_bytes() > 0);
+    }
+
+    #[test]
+    fn test_no_match_and_empty_pattern() {
+        let index = DnaIndex::build(b"AAAA", 1);
+        assert_eq!(index.count(b"G"), 0);
+        // 空パターンはどちらの鎖にもマッチしない（AliceIndex とは異なる）
+        assert_eq!(index.count(b""), 0);
+        assert_eq!(index.count_strand(b"", Strand::Reverse), 0);
+        assert!(index.locate_all(b"").is_empty());
+        assert!(!index.contains(b""));
+        assert_eq!(index.inner().count(b""), 5);
+        assert!(!index.contains(b"CC"));
+        // "TTT" の逆相補 "AAA" は逆鎖で 2 回
+        assert_eq!(index.count(b"TTT"), 2);
+    }
+}
@@ -0,0 +1,449 @@
+//! Document Index with Deletion (Tombstones)
+//!
+//! **Architecture**:
+//! - Documents are concatenated into one `AliceIndex`; `starts` records the
+//!   offset of each document. When some byte occurs in no document it is
+//!   placed between documents as the index's separator, so no match can
+//!   cross a boundary and `count` is a plain FM-index count; otherwise
+//!   matches crossing a boundary are dropped while locating
+//! - `delete` only flips a bit in the `tombstones` `BitVector`: O(1), and
+//!   deleted documents vanish from `count` / `locate` / `documents` at once
+//! - `compact` drops tombstoned documents physically, recovering their text
+//!   from the index itself (inverse BWT), so the input need not be kept
+//!
+//! Document ids are assigned in input order and stay stable across
+//! compaction.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::bitvec::BitVector;
+use crate::build::{self, BuildConfig, Progress};
+use crate::search::{AliceIndex, IndexOptions, LocateIter};
+
+/// 文書 ID（入力順に 0 から採番、削除や圧縮後も不変）。
+pub type DocId = usize;
+
+/// 文書単位のインデックス（削除対応）。
+pub struct DocumentIndex {
+    inner: AliceIndex,
+    options: IndexOptions,
+    /// `starts[slot]` = offset of the slot's document; last entry = text length
+    /// (separators included)
+    starts: Vec<usize>,
+    /// Byte between consecutive documents, if one occurs in none of them
+    separator: Option<u8>,
+    /// `ids[slot]` = external document id (strictly increasing)
+    ids: Vec<DocId>,
+    /// Bit per slot: set when the document is deleted
+    tombstones: BitVector,
+    deleted: usize,
+}
+
+impl DocumentIndex {
+    /// 文書列からインデックスを構築する。
+    #[must_use]
+    pub fn build(docs: &[&[u8]], sample_step: usize) -> Self {
+        Self::build_with_options(docs, &IndexOptions::with_sample_step(sample_step))
+    }
+
+    /// ビルドオプションを指定して構築する。
+    #[must_use]
+    pub fn build_with_options(docs: &[&[u8]], options: &IndexOptions) -> Self {
+        let mut text = Vec::with_capacity(docs.iter().map(|d| d.len()).sum());
+        let mut starts = Vec::with_capacity(docs.len() + 1);
+        for doc in docs {
+            starts.push(text.len());
+            text.extend_from_slice(doc);
+        }
+        starts.push(text.len());
+        Self::from_parts(&text, starts, (0..docs.len()).collect(), options)
+    }
+
+    /// 文書ごとのリーダーから構築する（呼び出し側で連結する必要はない）。
+    ///
+    /// # Errors
+    /// いずれかのリーダーの最初の I/O エラーを返す。
+    #[cfg(feature = "std")]
+    pub fn build_from_readers<R: std::io::Read>(
+        readers: impl IntoIterator<Item = R>,
+        options: &IndexOptions,
+    ) -> std::io::Result<Self> {
+        let mut text = Vec::new();
+        let mut starts = Vec::new();
+        for mut reader in readers {
+            starts.push(text.len());
+            reader.read_to_end(&mut text)?;
+        }
+        starts.push(text.len());
+        let ids = (0..starts.len() - 1).collect();
+        Ok(Self::from_parts(&text, starts, ids, options))
+    }
+
+    /// Build from the concatenated documents (`starts` without separators).
+    fn from_parts(
+        text: &[u8],
+        mut starts: Vec<usize>,
+        ids: Vec<DocId>,
+        options: &IndexOptions,
+    ) -> Self {
+        let mut used = [false; 256];
+        for &b in text {
+            used[b as usize] = true;
+        }
+        // Byte 0 shares code 0 with the sentinel, which stops `locate`'s LF
+        // walk early, so it is never used as the separator
+        let separator = (1..=u8::MAX).rev().find(|&b| !used[b as usize]);
+
+        let mut config = BuildConfig::from(options);
+        let joined;
+        let text = match separator {
+            Some(sep) if ids.len() > 1 => {
+                let mut buf = Vec::with_capacity(text.len() + ids.len() - 1);
+                for slot in 0..ids.len() {
+                    if slot > 0 {
+                        buf.push(sep);
+                    }
+                    buf.extend_from_slice(&text[starts[slot]..starts[slot + 1]]);
+                }
+                // Shift every start past the separators before it
+                for (slot, start) in starts.iter_mut().enumerate() {
+                    *start += slot.min(ids.len() - 1);
+                }
+                config.separator = Some(sep);
+                joined = buf;
+                &joined[..]
+            }
+            _ => text,
+        };
+
+        let mut tombstones = BitVector::new();
+        for _ in 0..ids.len() {
+            tombstones.push(false);
+        }
+        tombstones.build_index();
+        Self {
+            inner: build::uncancelled(AliceIndex::build_configured(
+                text,
+                &config,
+                &mut Progress::none(),
+            ))
+            .0,
+            options: *options,
+            starts,
+            separator: config.separator,
+            ids,
+            tombstones,
+            deleted: 0,
+        }
+    }
+
+    /// 文書を削除（トゥームストーン化）する。新たに削除した場合 `true`。
+    pub fn delete(&mut self, id: DocId) -> bool {
+        let Some(slot) = self.slot_of(id) else {
+            return false;
+        };
+        if self.tombstones.get(slot) {
+            return false;
+        }
+        self.tombstones.set(slot, true);
+        self.deleted += 1;
+        true
+    }
+
+    /// 文書が削除済み（または存在しない）か。
+    #[must_use]
+    pub fn is_deleted(&self, id: DocId) -> bool {
+        self.slot_of(id)
+            .is_none_or(|slot| self.tombstones.get(slot))
+    }
+
+    /// 削除済み文書を物理的に取り除いてインデックスを再構築する。
+    ///
+    /// テキストはインデックスから復元するため、元の入力は不要。
+    pub fn compact(&mut self) {
+        if self.deleted == 0 {
+            return;
+        }
+        let text = self.inner.extract_text();
+        let mut kept = Vec::with_capacity(text.len());
+        let mut starts = Vec::new();
+        let mut ids = Vec::new();
+        for slot in 0..self.ids.len() {
+            if self.tombstones.get(slot) {
+                continue;
+            }
+            starts.push(kept.len());
+            ids.push(self.ids[slot]);
+            kept.extend_from_slice(&text[self.starts[slot]..self.end_of(slot)]);
+        }
+        starts.push(kept.len());
+        *self = Self::from_parts(&kept, starts, ids, &self.options);
+    }
+
+    /// 削除されていない文書内のマッチ数。
+    ///
+    /// 空パターンは削除されていない文書の各バイト位置にマッチする
+    /// （＝生存文書の総バイト数）。削除がなく区切りバイトがあれば
+    /// FM-index の計数のみで済む。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        if pattern.is_empty() {
+            return (0..self.ids.len())
+                .filter(|&slot| !self.tombstones.get(slot))
+                .map(|slot| self.end_of(slot) - self.starts[slot])
+                .sum();
+        }
+        if self.deleted == 0 && self.separator.is_some() {
+            return self.inner.count(pattern);
+        }
+        self.locate(pattern).count()
+    }
+
+    /// 削除されていない文書内にマッチが存在するか。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        if self.deleted == 0 && self.separator.is_some() && !pattern.is_empty() {
+            return self.inner.contains(pattern);
+        }
+        self.locate(pattern).next().is_some()
+    }
+
+    /// `(文書 ID, 文書内オフセット)` を返すイテレータ。
+    #[must_use]
+    pub fn locate<'a>(&'a self, pattern: &'a [u8]) -> DocLocateIter<'a> {
+        DocLocateIter {
+            index: self,
+            inner: self.inner.locate(pattern),
+            pattern_len: pattern.len(),
+        }
+    }
+
+    /// 全マッチを `(文書 ID, 文書内オフセット)` の Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<(DocId, usize)> {
+        self.locate(pattern).collect()
+    }
+
+    /// パターンを含む文書 ID（昇順、重複なし）。
+    #[must_use]
+    pub fn documents(&self, pattern: &[u8]) -> Vec<DocId> {
+        let mut ids: Vec<DocId> = self.locate(pattern).map(|(id, _)| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// 削除されていない文書数。
+    #[must_use]
+    pub fn doc_count(&self) -> usize {
+        self.ids.len() - self.deleted
+    }
+
+    /// 削除済みで未圧縮の文書数。
+    #[must_use]
+    pub const fn deleted_count(&self) -> usize {
+        self.deleted
+    }
+
+    /// インデックス済みテキストの長さ（未圧縮の削除文書を含み、区切りバイトは含まない）。
+    #[must_use]
+    pub fn text_len(&self) -> usize {
+        let separators = match self.separator {
+            Some(_) => self.ids.len().saturating_sub(1),
+            None => 0,
+        };
+        self.inner.text_len() - separators
+    }
+
+    /// インデックスの概算バイトサイズ。
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        self.inner.size_bytes()
+            + (self.starts.len() + self.ids.len()) * core::mem::size_of::<usize>()
+            + self.tombstones.len().div_ceil(512) * 72
+    }
+
+    fn slot_of(&self, id: DocId) -> Option<usize> {
+        self.ids.binary_search(&id).ok()
+    }
+
+    /// Slot of the document containing text offset `pos` (`pos < text_len`).
+    #[inline]
+    fn slot_at(&self, pos: usize) -> usize {
+        self.starts.partition_point(|&s| s <= pos) - 1
+    }
+
+    /// End of the slot's document, excluding the separator after it.
+    #[inline]
+    fn end_of(&self, slot: usize) -> usize {
+        let end = self.starts[slot + 1];
+        match self.separator {
+            Some(_) if slot + 1 < self.ids.len() => end - 1,
+            _ => end,
+        }
+    }
+
+    /// `(id, offset)` of a match, or `None` if it crosses a document
+    /// boundary, lies in a deleted document, or is the empty pattern's
+    /// match on a separator or the end of the text.
+    fn resolve(&self, pos: usize, pattern_len: usize) -> Option<(DocId, usize)> {
+        if pos >= self.inner.text_len() {
+            return None;
+        }
+        let slot = self.slot_at(pos);
+        let end = self.end_of(slot);
+        if pos >= end || pos + pattern_len > end || self.tombstones.get(slot) {
+            return None;
+        }
+        Some((self.ids[slot], pos - self.starts[slot]))
+    }
+}
+
+/// Iterator over `(document id, offset in document)` matches.
+pub struct DocLocateIter<'a> {
+    index: &'a DocumentIndex,
+    inner: LocateIter<'a>,
+    pattern_len: usize,
+}
+
+impl Iterator for DocLocateIter<'_> {
+    type Item = (DocId, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+        let len = self.pattern_len;
+        self.inner.by_ref().find_map(|pos| index.resolve(pos, len))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DocumentIndex {
+        DocumentIndex::build(&[b"apple pie", b"banana", b"", b"apple tart"], 2)
+    }
+
+    #[test]
+    fn test_matches_stay_inside_documents() {
+        let index = sample();
+        assert_eq!(index.count(b"apple"), 2);
+        // "pie" + "banana" の境界を跨ぐマッチは報告しない（区切りバイトで分断済み）
+        assert_eq!(index.inner.count(b"pieb"), 0);
+        assert_eq!(index.count(b"pieb"), 0);
+
+        let mut hits = index.locate_all(b"a");
+        hits.sort_unstable();
+        assert_eq!(hits, vec![(0, 0), (1, 1), (1, 3), (1, 5), (3, 0), (3, 7)]);
+        assert_eq!(index.documents(b"ap"), vec![0, 3]);
+    }
+
+    #[test]
+    fn test_empty_pattern_counts_live_bytes() {
+        let index = DocumentIndex::build(&[b"ab", b"cd"], 4);
+        assert_eq!(index.count(b""), 4);
+        assert_eq!(index.locate_all(b"").len(), 4);
+
+        let mut index = sample();
+        assert_eq!(index.count(b""), 25);
+        index.delete(1);
+        assert_eq!(index.count(b""), 19);
+        assert_eq!(index.locate(b"").count(), 19);
+        assert_eq!(index.documents(b""), vec![0, 3]);
+    }
+
+    #[test]
+    fn test_empty_corpus() {
+        let index = DocumentIndex::build(&[], 4);
+        assert_eq!(index.count(b""), 0);
+        assert_eq!(index.count(b"a"), 0);
+        assert!(index.locate_all(b"").is_empty());
+        assert_eq!(index.doc_count(), 0);
+        assert_eq!(index.text_len(), 0);
+    }
+
+    #[test]
+    fn test_count_without_free_separator_byte() {
+        // 全 256 バイト値を使う文書群では区切りを置けず、locate で境界を除外する
+        let all: Vec<u8> = (0..=u8::MAX).collect();
+        let index = DocumentIndex::build(&[&all, b"ab", b"cd"], 4);
+        assert!(index.separator.is_none());
+        assert_eq!(index.count(b"bc"), 1);
+        assert_eq!(index.count(b"dab"), 0);
+        assert_eq!(index.count(b""), 260);
+
+        // 区切りあり: 境界を跨ぐパターンは FM-index 上でも存在しない
+        let index = DocumentIndex::build(&[b"ab", b"cd"], 4);
+        assert_eq!(index.separator, Some(u8::MAX));
+        assert_eq!(index.count(b"bc"), 0);
+        assert_eq!(index.locate_all(b"cd"), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_nul_is_never_the_separator() {
+        // 1..=255 をすべて使うと空きは 0 だけだが、0 は番兵と同じコードなので使わない
+        let high: Vec<u8> = (1..=u8::MAX).collect();
+        let index = DocumentIndex::build(&[&high, b"xyz", b"abcxyz"], 64);
+        assert!(index.separator.is_none());
+        let mut hits = index.locate_all(b"abc");
+        hits.sort_unstable();
+        assert_eq!(hits, vec![(0, 96), (2, 0)]);
+        let mut hits = index.locate_all(b"xyz");
+        hits.sort_unstable();
+        assert_eq!(hits, vec![(0, 119), (1, 0), (2, 3)]);
+        assert_eq!(index.documents(b"xyz"), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_build_from_readers_keeps_boundaries() {
+        let docs: [&[u8]; 4] = [b"apple pie", b"banana", b"", b"apple tart"];
+        let index =
+            DocumentIndex::build_from_readers(docs, &IndexOptions::with_sample_step(2)).unwrap();
+        assert_eq!(index.doc_count(), 4);
+        assert_eq!(index.documents(b"apple"), vec![0, 3]);
+        assert_eq!(index.count(b"pieb"), 0);
+        assert_eq!(index.locate_all(b"tart"), sample().locate_all(b"tart"));
+    }
+
+    #[test]
+    fn test_delete_filters_results() {
+        let mut index = sample();
+        assert!(index.delete(0));
+        assert!(!index.delete(0));
+        assert!(!index.delete(42));
+        assert!(index.is_deleted(0));
+        assert!(!index.is_deleted(1));
+
+        assert_eq!(index.count(b"apple"), 1);
+        assert_eq!(index.documents(b"apple"), vec![3]);
+        assert!(!index.contains(b"pie"));
+        assert_eq!(index.doc_count(), 3);
+        assert_eq!(index.deleted_count(), 1);
+    }
+
+    #[test]
+    fn test_compact_drops_deleted_text() {
+        let mut index = sample();
+        let before = index.text_len();
+        index.delete(1);
+        index.compact();
+
+        assert_eq!(index.deleted_count(), 0);
+        assert_eq!(index.text_len(), before - 6);
+        assert_eq!(index.count(b"banana"), 0);
+        // ID は圧縮後も不変
+        assert_eq!(index.documents(b"tart"), vec![3]);
+        assert_eq!(index.locate_all(b"tart"), vec![(3, 6)]);
+        assert!(index.is_deleted(1));
+        assert!(index.delete(3));
+        index.compact();
+        assert_eq!(index.locate_all(b"apple"), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,584 @@
+//! External-Memory Index Construction
+//!
+//! **Architecture** (block-wise BWT merging, Ferragina–Gagie–Manzini style):
+//! - The text is read from a file in blocks of `memory_budget / 17` bytes,
+//!   from the last block to the first
+//! - The BWT of the suffixes processed so far lives in a temporary file, with
+//!   a Wavelet Matrix over it for rank queries
+//! - For each new block, backward search over the old BWT gives every block
+//!   suffix its rank among the old suffixes; SA-IS over the block, with the
+//!   suffix after the block encoded by its old rank, orders suffixes of
+//!   equal rank in linear time. One sequential pass then merges the block's
+//!   BWT characters and SA samples into the files
+//! - The final Wavelet Matrix is built layer by layer from the BWT file,
+//!   distributing each layer into two files instead of two RAM buffers
+//!
+//! The result is bit-identical to `AliceIndex::build_with_options`. The
+//! memory budget bounds the block working set only. On top of it, the
+//! Wavelet Matrix over the partial BWT is rebuilt after every block and
+//! kept in RAM: 9/8 bytes per merged byte, so about 1.1 × N by the last
+//! block (the size of the final index's Wavelet Matrix), plus I/O buffers.
+//! Each block rereads the partial BWT, so time is O(N² / block length).
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::bitvec::BitVector;
+use crate::build::BuildConfig;
+use crate::bwt::{self, SaisText};
+use crate::search::{AliceIndex, Alphabet, IndexOptions};
+use crate::wavelet::{WaveletMatrix, LAYERS};
+
+/// Working bytes per block byte: text + old rank + sort order.
+const BYTES_PER_BLOCK_BYTE: usize = 1 + 2 * core::mem::size_of::<usize>();
+
+/// Buffer size for the sequential temp-file passes.
+const IO_BUFFER: usize = 1 << 16;
+
+/// ディスクを使うインデックスビルダー（メモリに載らないコーパス向け）。
+///
+/// 一時ファイルは `temp_dir` 直下の専用ディレクトリに作成し、
+/// ビルド終了時（エラー時も含む）に削除する。
+#[derive(Clone, Debug)]
+pub struct ExternalBuilder {
+    temp_dir: PathBuf,
+    memory_budget: usize,
+    options: IndexOptions,
+}
+
+impl ExternalBuilder {
+    /// 一時ディレクトリと作業メモリ量（バイト）を指定して作成する。
+    ///
+    /// 作業メモリ量が制限するのはブロックの作業領域だけで、これとは別に
+    /// マージ済み BWT のランク辞書（最後には入力の約 1.1 倍）を RAM に置く。
+    #[must_use]
+    pub fn new(temp_dir: impl Into<PathBuf>, memory_budget: usize) -> Self {
+        Self {
+            temp_dir: temp_dir.into(),
+            memory_budget,
+            options: IndexOptions::default(),
+        }
+    }
+
+    /// ビルドオプションを指定する（`threads` は使用しない）。
+    #[must_use]
+    pub const fn with_options(mut self, options: &IndexOptions) -> Self {
+        self.options = *options;
+        self
+    }
+
+    /// 1 ブロックのテキスト長（作業メモリ量から決まる）。
+    #[must_use]
+    pub const fn block_len(&self) -> usize {
+        let len = self.memory_budget / BYTES_PER_BLOCK_BYTE;
+        if len == 0 {
+            1
+        } else {
+            len
+        }
+    }
+
+    /// ファイルの内容をインデックス化する。
+    ///
+    /// # Errors
+    /// 入力ファイルの読み込みや一時ファイルの作成・読み書きに失敗した場合。
+    pub fn build_file(&self, path: impl AsRef<Path>) -> io::Result<AliceIndex> {
+        let mut input = File::open(path)?;
+        let n = usize::try_from(input.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large"))?;
+        let scratch = Scratch::create(&self.temp_dir)?;
+        let step = self.options.sample_step.max(1);
+
+        let mut merge = Merge::new(&scratch, n, step)?;
+        let block_len = self.block_len();
+        let mut block = Vec::with_capacity(block_len.min(n));
+        let mut end = n;
+        while end > 0 {
+            let start = end.saturating_sub(block_len);
+            block.resize(end - start, 0);
+            input.seek(SeekFrom::Start(start as u64))?;
+            input.read_exact(&mut block)?;
+            merge.add_block(&block, start, start > 0)?;
+            end = start;
+        }
+        merge.finish(&self.options)
+    }
+}
+
+/// Temporary directory removed on drop.
+struct Scratch {
+    dir: PathBuf,
+}
+
+impl Scratch {
+    fn create(parent: &Path) -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = parent.join(format!("alice-search-{}-{id}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// State of the suffixes `T[start..]` merged so far.
+///
+/// Per SA row, in row order: `bwt` holds the preceding byte (0 on the row of
+/// `T[start..]`, whose preceding byte is not read yet), `marks` one byte
+/// (1 = sampled row) and `samples` the sampled positions as `u64` LE.
+struct Merge<'a> {
+    scratch: &'a Scratch,
+    /// Rows merged so far (= suffixes, including the empty one)
+    rows: usize,
+    /// Row of the suffix `T[start..]`
+    start_row: usize,
+    /// Byte histogram of `T[start..]`
+    counts: [usize; 256],
+    /// First byte of `T[start..]` (`None` while it is empty)
+    head: Option<u8>,
+    /// Rank directory over the `bwt` file
+    wm: WaveletMatrix,
+    step: usize,
+}
+
+impl<'a> Merge<'a> {
+    /// Start with the empty suffix of a text of length `n`.
+    fn new(scratch: &'a Scratch, n: usize, step: usize) -> io::Result<Self> {
+        File::create(scratch.path("bwt"))?.write_all(&[0])?;
+        File::create(scratch.path("marks"))?.write_all(&[u8::from(n.is_multiple_of(step))])?;
+        let mut samples = File::create(scratch.path("samples"))?;
+        if n.is_multiple_of(step) {
+            samples.write_all(&(n as u64).to_le_bytes())?;
+        }
+        Ok(Self {
+            scratch,
+            rows: 1,
+            start_row: 0,
+            counts: [0; 256],
+            head: None,
+            wm: WaveletMatrix::build(&[0]),
+            step,
+        })
+    }
+
+    /// Merge the suffixes starting in `block` = `T[start..start + len]`.
+    /// `more` = blocks before this one remain (rebuild the rank directory).
+    fn add_block(&mut self, block: &[u8], start: usize, more: bool) -> io::Result<()> {
+        let len = block.len();
+
+        // Rank of every block suffix among the old suffixes (backward search)
+        let mut c_old = [0usize; 256];
+        let mut sum = 1; // the empty suffix sorts first
+        for (c, &count) in c_old.iter_mut().zip(&self.counts) {
+            *c = sum;
+            sum += count;
+        }
+        let mut rank = vec![0usize; len + 1];
+        rank[len] = self.start_row;
+        for x in (0..len).rev() {
+            let c = block[x];
+            let next = rank[x + 1];
+            // The placeholder 0 on `start_row` is not a real byte
+            let placeholder = usize::from(c == 0 && next > self.start_row);
+            rank[x] = c_old[c as usize] + self.wm.rank(c, next) - placeholder;
+        }
+
+        // Order of the block suffixes: SA-IS over the block, with the
+        // boundary suffix `T[start + len..]` encoded by its old rank. The
+        // order agrees with the true one, so `rank` is non-decreasing along it
+        let view = BlockText {
+            block,
+            rank: &rank,
+            start_row: self.start_row,
+            boundary: self.head,
+        };
+        let mut order = bwt::build_suffix_array_view(&view, BlockText::ALPHA);
+        order.retain(|&j| j < len);
+
+        // One sequential pass: old rows interleaved with the block rows
+        let mut old = RowReader::open(self.scratch)?;
+        let mut new = RowWriter::create(self.scratch)?;
+        let last = block[len - 1];
+        let mut row = 0;
+        let mut new_start_row = 0;
+        for (i, &j) in order.iter().enumerate() {
+            while row < rank[j] {
+                old.copy_row(&mut new, row == self.start_row, last)?;
+                row += 1;
+            }
+            let pos = start + j;
+            let bwt = if j == 0 {
+                new_start_row = rank[j] + i;
+                0
+            } else {
+                block[j - 1]
+            };
+            new.push(bwt, pos.is_multiple_of(self.step).then_some(pos))?;
+        }
+        while row < self.rows {
+            old.copy_row(&mut new, row == self.start_row, last)?;
+            row += 1;
+        }
+        drop(old);
+        new.finish(self.scratch)?;
+
+        self.rows += len;
+        self.start_row = new_start_row;
+        self.head = Some(block[0]);
+        for &b in block {
+            self.counts[b as usize] += 1;
+        }
+        if more {
+            self.wm = stream_wavelet(self.scratch, self.rows, &identity_map(), LAYERS)?;
+        }
+        Ok(())
+    }
+
+    /// Assemble the `AliceIndex` from the merged files.
+    fn finish(self, options: &IndexOptions) -> io::Result<AliceIndex> {
+        let alphabet = if options.compact_alphabet {
+            let mut used = [false; 256];
+            for (u, &count) in used.iter_mut().zip(&self.counts) {
+                *u = count > 0;
+            }
+            Alphabet::from_used(&used)
+        } else {
+            Alphabet::identity()
+        };
+        let mut map = [0u8; 256];
+        for (b, code) in map.iter_mut().enumerate() {
+            *code = alphabet.code(b as u8);
+        }
+
+        // C-table over the coded BWT: every text byte plus the sentinel
+        let mut code_counts = [0usize; 256];
+        code_counts[0] = 1;
+        for (b, &count) in self.counts.iter().enumerate() {
+            code_counts[map[b] as usize] += count;
+        }
+        let mut c_table = [0usize; 256];
+        let mut sum = 0;
+        for (c, &count) in c_table.iter_mut().zip(&code_counts) {
+            *c = sum;
+            sum += count;
+        }
+
+        // Layer count from the codes in use, as `WaveletMatrix::build` does
+        let max = (0..256)
+            .filter(|&b| self.counts[b] > 0)
+            .map(|b| map[b])
+            .max();
+        let bits = (u8::BITS - max.unwrap_or(0).leading_zeros()).max(1) as usize;
+        let wm = stream_wavelet(self.scratch, self.rows, &map, bits)?;
+
+        let mut words = vec![0u64; self.rows.div_ceil(64)];
+        let mut marks =
+            BufReader::with_capacity(IO_BUFFER, File::open(self.scratch.path("marks"))?);
+        let mut sampled = 0;
+        let mut chunk = vec![0u8; IO_BUFFER];
+        let mut row = 0;
+        while row < self.rows {
+            let take = chunk.len().min(self.rows - row);
+            marks.read_exact(&mut chunk[..take])?;
+            for &mark in &chunk[..take] {
+                if mark != 0 {
+                    words[row / 64] |= 1 << (row % 64);
+                    sampled += 1;
+                }
+                row += 1;
+            }
+        }
+        let mut samples = Vec::with_capacity(sampled);
+        let mut reader =
+            BufReader::with_capacity(IO_BUFFER, File::open(self.scratch.path("samples"))?);
+        let mut value = [0u8; 8];
+        for _ in 0..sampled {
+            reader.read_exact(&mut value)?;
+            samples.push(u64::from_le_bytes(value) as usize);
+        }
+
+        Ok(AliceIndex::from_parts(
+            alphabet,
+            wm,
+            c_table,
+            BuildConfig::from(options),
+            &samples,
+            BitVector::from_words(&words, self.rows),
+        ))
+    }
+}
+
+/// The block `T[start..start + len]` as SA-IS symbols, followed by one
+/// symbol for the boundary suffix `B = T[start + len..]`.
+///
+/// Byte `c` at `x` becomes `3c + 1` if `T[start + x..] < B` (its old rank is
+/// at most the row of `B`) and `3c + 3` otherwise; `B` itself becomes
+/// `3b + 2` for its first byte `b`. Unequal symbols then order the suffixes
+/// correctly, and `B` is never compared past itself. An empty `B` (the last
+/// block of the text) is the sentinel.
+struct BlockText<'a> {
+    block: &'a [u8],
+    rank: &'a [usize],
+    start_row: usize,
+    boundary: Option<u8>,
+}
+
+impl BlockText<'_> {
+    const ALPHA: usize = 3 * 256 + 1;
+}
+
+impl SaisText for BlockText<'_> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.block.len() + usize::from(self.boundary.is_some()) + 1
+    }
+
+    #[inline(always)]
+    fn sym(&self, i: usize) -> usize {
+        match self.block.get(i) {
+            Some(&c) => 3 * c as usize + if self.rank[i] > self.start_row { 3 } else { 1 },
+            None if i == self.block.len() => self.boundary.map_or(0, |b| 3 * b as usize + 2),
+            None => 0,
+        }
+    }
+}
+
+/// Sequential reader over the current row files.
+struct RowReader {
+    bwt: BufReader<File>,
+    marks: BufReader<File>,
+    samples: BufReader<File>,
+}
+
+impl RowReader {
+    fn open(scratch: &Scratch) -> io::Result<Self> {
+        let open = |name| -> io::Result<BufReader<File>> {
+            Ok(BufReader::with_capacity(
+                IO_BUFFER,
+                File::open(scratch.path(name))?,
+            ))
+        };
+        Ok(Self {
+            bwt: open("bwt")?,
+            marks: open("marks")?,
+            samples: open("samples")?,
+        })
+    }
+
+    /// Copy one row; the placeholder row gets the now-known byte `fill`.
+    fn copy_row(&mut self, out: &mut RowWriter, placeholder: bool, fill: u8) -> io::Result<()> {
+        let mut byte = [0u8; 1];
+        self.bwt.read_exact(&mut byte)?;
+        let bwt = if placeholder { fill } else { byte[0] };
+        self.marks.read_exact(&mut byte)?;
+        let sample = if byte[0] == 0 {
+            None
+        } else {
+            let mut value = [0u8; 8];
+            self.samples.read_exact(&mut value)?;
+            Some(u64::from_le_bytes(value) as usize)
+        };
+        out.push(bwt, sample)
+    }
+}
+
+/// Sequential writer of the next row files (renamed over the current ones).
+struct RowWriter {
+    bwt: BufWriter<File>,
+    marks: BufWriter<File>,
+    samples: BufWriter<File>,
+}
+
+impl RowWriter {
+    fn create(scratch: &Scratch) -> io::Result<Self> {
+        let create = |name| -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::with_capacity(
+                IO_BUFFER,
+                File::create(scratch.path(name))?,
+            ))
+        };
+        Ok(Self {
+            bwt: create("bwt.next")?,
+            marks: create("marks.next")?,
+            samples: create("samples.next")?,
+        })
+    }
+
+    fn push(&mut self, bwt: u8, sample: Option<usize>) -> io::Result<()> {
+        self.bwt.write_all(&[bwt])?;
+        self.marks.write_all(&[u8::from(sample.is_some())])?;
+        if let Some(pos) = sample {
+            self.samples.write_all(&(pos as u64).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn finish(self, scratch: &Scratch) -> io::Result<()> {
+        for (mut writer, name) in [
+            (self.bwt, "bwt"),
+            (self.marks, "marks"),
+            (self.samples, "samples"),
+        ] {
+            writer.flush()?;
+            drop(writer);
+            fs::rename(scratch.path(&format!("{name}.next")), scratch.path(name))?;
+        }
+        Ok(())
+    }
+}
+
+fn identity_map() -> [u8; 256] {
+    core::array::from_fn(|b| b as u8)
+}
+
+/// Build a `bits`-layer Wavelet Matrix over `map[b]` for the bytes of the
+/// `bwt` file.
+///
+/// Same layer-by-layer scheme as `WaveletMatrix::build`, but each layer
+/// streams its input and distributes it into a zeros file and a ones file,
+/// which are read back in that order for the next layer.
+fn stream_wavelet(
+    scratch: &Scratch,
+    len: usize,
+    map: &[u8; 256],
+    bits: usize,
+) -> io::Result<WaveletMatrix> {
+    let mut layers: [BitVector; LAYERS] = core::array::from_fn(|_| BitVector::new());
+    let mut zeros = [0usize; LAYERS];
+    let mut chunk = vec![0u8; IO_BUFFER];
+    let mut inputs = vec![scratch.path("bwt")];
+    for (pass, d) in (0..bits).rev().enumerate() {
+        let bit_mask = 1u8 << d;
+        let outputs = [
+            scratch.path(&format!("wm{}-0", pass % 2)),
+            scratch.path(&format!("wm{}-1", pass % 2)),
+        ];
+        let mut zero_out = BufWriter::with_capacity(IO_BUFFER, File::create(&outputs[0])?);
+        let mut one_out = BufWriter::with_capacity(IO_BUFFER, File::create(&outputs[1])?);
+        let mut words = vec![0u64; len.div_ceil(64)];
+        let mut i = 0;
+        for input in &inputs {
+            let mut reader = File::open(input)?;
+            loop {
+                let read = reader.read(&mut chunk)?;
+                if read == 0 {
+                    break;
+                }
+                for byte in &mut chunk[..read] {
+                    // Map raw bytes to codes on the first pass only
+                    if pass == 0 {
+                        *byte = map[*byte as usize];
+                    }
+                    if *byte & bit_mask != 0 {
+                        words[i / 64] |= 1 << (i % 64);
+                    } else {
+                        zeros[d] += 1;
+                    }
+                    i += 1;
+                }
+                for &byte in &chunk[..read] {
+                    if byte & bit_mask != 0 {
+                        one_out.write_all(&[byte])?;
+                    } else {
+                        zero_out.write_all(&[byte])?;
+                    }
+                }
+            }
+        }
+        zero_out.flush()?;
+        one_out.flush()?;
+        layers[d] = BitVector::from_words(&words, len);
+        inputs = outputs.to_vec();
+    }
+    Ok(WaveletMatrix::from_layers(layers, zeros, bits, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_external(text: &[u8], budget: usize, options: &IndexOptions) -> AliceIndex {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corpus");
+        fs::write(&path, text).unwrap();
+        ExternalBuilder::new(dir.path(), budget)
+            .with_options(options)
+            .build_file(&path)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_external_build_is_bit_identical() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| b"abracadabra mississippi "[(i * 5 % 24) as usize] ^ u8::from(i % 11 == 0))
+            .collect();
+        for budget in [17 * 3, 17 * 100, 17 * 1024, 1 << 20] {
+            for options in [
+                IndexOptions::with_sample_step(3),
+                IndexOptions {
+                    compact_alphabet: false,
+                    ..IndexOptions::with_sample_step(8)
+                },
+            ] {
+                let external = build_external(&text[..1000], budget, &options);
+                assert!(external == AliceIndex::build_with_options(&text[..1000], &options));
+            }
+        }
+        let options = IndexOptions::default();
+        let external = build_external(&text, 17 * 256, &options);
+        assert!(external == AliceIndex::build_with_options(&text, &options));
+        assert_eq!(
+            external.count(b"abra"),
+            AliceIndex::build(&text, 16).count(b"abra")
+        );
+    }
+
+    #[test]
+    fn test_external_build_repetitive_and_binary() {
+        let runs = vec![b'a'; 700];
+        let binary: Vec<u8> = (0..900u32).map(|i| (i * i % 256) as u8).collect();
+        for text in [&runs[..], &binary, b"\x00\x00\x01\x00", b"x", b""] {
+            let options = IndexOptions::with_sample_step(2);
+            let external = build_external(text, 17 * 64, &options);
+            assert!(external == AliceIndex::build_with_options(text, &options));
+        }
+    }
+
+    #[test]
+    fn test_external_build_long_runs_across_blocks() {
+        // 長い連続はブロック内ソートが線形でないと極端に遅くなる
+        let mut runs = vec![b'a'; 40_000];
+        runs[25_000] = b'b';
+        let periodic: Vec<u8> = b"ab".iter().copied().cycle().take(30_000).collect();
+        for text in [&runs[..], &periodic] {
+            let options = IndexOptions::with_sample_step(32);
+            let external = build_external(text, 17 * 16_000, &options);
+            assert!(external == AliceIndex::build_with_options(text, &options));
+        }
+    }
+
+    #[test]
+    fn test_scratch_directory_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corpus");
+        fs::write(&path, b"hello external world").unwrap();
+        let temp = dir.path().join("tmp");
+        let index = ExternalBuilder::new(&temp, 170).build_file(&path).unwrap();
+        assert_eq!(index.count(b"l"), 4);
+        assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+        assert!(ExternalBuilder::new(&temp, 170)
+            .build_file(dir.path().join("missing"))
+            .is_err());
+    }
+}
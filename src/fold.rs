@@ -0,0 +1,400 @@
+//! Text Folding with Offset Mapping
+//!
+//! **Architecture**:
+//! - Folding runs unit by unit (a byte, a char, or a cluster of chars)
+//! - `OffsetMap` records only the units whose folded form changes length
+//!   or char count, so ASCII-only or length-preserving text costs nothing
+//! - Folded → original offsets: one binary search over the anchors
+//!
+//! Case-insensitive, normalized and other folded indexes build an
+//! `AliceIndex` over the folded text and report positions through the map.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::casefold;
+use crate::search::LocateIter;
+
+/// 1 ユニット分の折り畳み結果 (長さまたは文字数が変わる場合のみ記録)。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Anchor {
+    /// Folded offset of the unit
+    folded: usize,
+    /// Original offset of the unit
+    original: usize,
+    /// Folded length of the unit
+    folded_len: usize,
+    /// Original length of the unit
+    original_len: usize,
+}
+
+/// 折り畳み後テキストの位置 → 元テキストの位置の対応表。
+///
+/// 長さまたは文字数の変わったユニットだけをアンカーとして保持する。
+/// ユニットの途中を指す位置はユニット先頭（元テキスト）に丸められる。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OffsetMap {
+    anchors: Vec<Anchor>,
+    folded_len: usize,
+    original_len: usize,
+}
+
+impl OffsetMap {
+    /// 恒等写像（長さ `len` のテキスト）。
+    #[must_use]
+    pub const fn identity(len: usize) -> Self {
+        Self {
+            anchors: Vec::new(),
+            folded_len: len,
+            original_len: len,
+        }
+    }
+
+    /// 折り畳み後の位置を元テキストの位置に変換する。O(log anchors)
+    #[must_use]
+    pub fn to_original(&self, folded: usize) -> usize {
+        let idx = self.anchors.partition_point(|a| a.folded <= folded);
+        if idx == 0 {
+            return folded;
+        }
+        let a = &self.anchors[idx - 1];
+        if folded < a.folded + a.folded_len {
+            a.original
+        } else {
+            a.original + a.original_len + (folded - a.folded - a.folded_len)
+        }
+    }
+
+    /// 恒等写像か（アンカーなし）。
+    #[must_use]
+    pub fn is_identity(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// 折り畳み後テキストの長さ。
+    #[must_use]
+    pub const fn folded_len(&self) -> usize {
+        self.folded_len
+    }
+
+    /// 元テキストの長さ。
+    #[must_use]
+    pub const fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    /// 対応表の概算バイトサイズ。
+    #[must_use]
+    pub const fn size_bytes(&self) -> usize {
+        self.anchors.len() * core::mem::size_of::<Anchor>()
+    }
+}
+
+/// Iterator for folded-index locate results (original-text byte offsets).
+pub struct FoldedLocateIter<'a> {
+    inner: LocateIter<'a>,
+    offsets: &'a OffsetMap,
+}
+
+impl<'a> FoldedLocateIter<'a> {
+    /// 折り畳み後テキスト上の `LocateIter` を元テキストの位置に変換する。
+    #[must_use]
+    pub const fn new(inner: LocateIter<'a>, offsets: &'a OffsetMap) -> Self {
+        Self { inner, offsets }
+    }
+}
+
+impl Iterator for FoldedLocateIter<'_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|pos| self.offsets.to_original(pos))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for FoldedLocateIter<'_> {}
+
+/// ユニット単位でテキストを折り畳み、`OffsetMap` を構築する。
+///
+/// `unit(rest, out)` は `rest` 先頭の 1 ユニットを `out` に折り畳んで追記し、
+/// 消費したバイト数 (≥ 1) を返す。
+pub(crate) fn fold_units<F>(text: &[u8], mut unit: F) -> (Vec<u8>, OffsetMap)
+where
+    F: FnMut(&[u8], &mut Vec<u8>) -> usize,
+{
+    let mut out = Vec::with_capacity(text.len());
+    let mut anchors = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        let folded = out.len();
+        let consumed = unit(&text[i..], &mut out).max(1);
+        let produced = out.len() - folded;
+        // Multi-char output (e.g. "ß" → "ss") needs an anchor even when the
+        // byte length is unchanged: its inner char starts map to the unit start.
+        let multi_char = out[folded..].iter().skip(1).any(|&b| b & 0xC0 != 0x80);
+        if produced != consumed || multi_char {
+            anchors.push(Anchor {
+                folded,
+                original: i,
+                folded_len: produced,
+                original_len: consumed,
+            });
+        }
+        i += consumed;
+    }
+    let map = OffsetMap {
+        anchors,
+        folded_len: out.len(),
+        original_len: text.len(),
+    };
+    (out, map)
+}
+
+/// 先頭の UTF-8 文字をデコードする。不正なシーケンスなら `None`。
+#[inline]
+#[must_use]
+pub(crate) fn decode_utf8(bytes: &[u8]) -> Option<(char, usize)> {
+    let first = *bytes.first()?;
+    let len = match first {
+        0x00..=0x7F => return Some((first as char, 1)),
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let s = core::str::from_utf8(bytes.get(..len)?).ok()?;
+    s.chars().next().map(|c| (c, len))
+}
+
+/// 文字を UTF-8 で追記する。
+#[inline]
+pub(crate) fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut buf = [0u8; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+/// 大文字小文字の折り畳み方式。
+///
+/// Unicode の折り畳みは `CaseFolding.txt` (Unicode 14.0) に従う。
+/// `ς`/`σ`/`Σ`、`ſ`/`s`、`K` (ケルビン記号)/`k` は同一視されるが、
+/// トルコ語用 (`T`) の対応は使わないため `ı` は `i` と区別される。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CaseFolding {
+    /// ASCII の `A-Z` のみ。非 ASCII バイトはそのまま（バイト長不変）。
+    #[default]
+    Ascii,
+    /// 1 文字 → 1 文字の単純折り畳み (`Ä` → `ä`, `ẞ` → `ß`)。状態 `C` + `S`。
+    Simple,
+    /// 文字数が変わる完全折り畳み (`ß` → `ss`, `ﬁ` → `fi`)。状態 `C` + `F`。
+    ///
+    /// 検索は折り畳み後のテキスト上で行う。展開された文字の途中から
+    /// 始まるマッチも 1 件と数え (`ß` の中の `s` は 2 件)、その位置は
+    /// 元の文字の先頭に丸めるため、`locate` は同じ位置を複数回返し得る。
+    Full,
+}
+
+impl CaseFolding {
+    /// 1 文字を折り畳み、結果の各文字を `emit` に渡す。
+    #[inline]
+    pub fn fold_char(self, c: char, emit: &mut dyn FnMut(char)) {
+        match self {
+            Self::Ascii => emit(c.to_ascii_lowercase()),
+            Self::Simple => emit(casefold::simple(c)),
+            Self::Full => casefold::full(c, emit),
+        }
+    }
+
+    /// テキストを折り畳み、`OffsetMap` と共に返す。
+    ///
+    /// 不正な UTF-8 バイトはそのまま通過する。
+    #[must_use]
+    pub fn fold(self, text: &[u8]) -> (Vec<u8>, OffsetMap) {
+        if self == Self::Ascii {
+            let folded = text.iter().map(u8::to_ascii_lowercase).collect();
+            return (folded, OffsetMap::identity(text.len()));
+        }
+        fold_units(text, |rest, out| match decode_utf8(rest) {
+            Some((c, len)) => {
+                self.fold_char(c, &mut |f| push_char(out, f));
+                len
+            }
+            None => {
+                out.push(rest[0]);
+                1
+            }
+        })
+    }
+}
+
+/// Folded bytes of a pattern in reverse order, computed without allocation.
+///
+/// Walks the pattern backwards unit by unit. Segmentation matches
+/// `fold_units`: a unit is a valid UTF-8 char, otherwise a single byte,
+/// because a valid char always starts at the nearest non-continuation byte.
+pub(crate) struct FoldRev<'a> {
+    pattern: &'a [u8],
+    folding: CaseFolding,
+    /// Folded bytes of the current unit, consumed from the back
+    buf: [u8; 12],
+    buf_len: usize,
+}
+
+impl<'a> FoldRev<'a> {
+    pub(crate) const fn new(pattern: &'a [u8], folding: CaseFolding) -> Self {
+        Self {
+            pattern,
+            folding,
+            buf: [0; 12],
+            buf_len: 0,
+        }
+    }
+
+    /// Pops the last unit of the remaining pattern and folds it into `buf`.
+    fn refill(&mut self) {
+        let end = self.pattern.len();
+        let start = (end.saturating_sub(4)..end)
+            .rev()
+            .find(|&i| self.pattern[i] & 0xC0 != 0x80);
+        let unit = start.and_then(|s| match decode_utf8(&self.pattern[s..]) {
+            Some((c, len)) if s + len == end => Some((c, s)),
+            _ => None,
+        });
+        let Some((c, s)) = unit else {
+            self.buf[0] = self.pattern[end - 1];
+            self.buf_len = 1;
+            self.pattern = &self.pattern[..end - 1];
+            return;
+        };
+        self.pattern = &self.pattern[..s];
+        // Full folding emits at most 3 chars (`to_uppercase` bound)
+        let (buf, len) = (&mut self.buf, &mut self.buf_len);
+        *len = 0;
+        self.folding.fold_char(c, &mut |f| {
+            let mut tmp = [0u8; 4];
+            let bytes = f.encode_utf8(&mut tmp).as_bytes();
+            buf[*len..*len + bytes.len()].copy_from_slice(bytes);
+            *len += bytes.len();
+        });
+    }
+}
+
+impl Iterator for FoldRev<'_> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        if self.buf_len == 0 {
+            if self.pattern.is_empty() {
+                return None;
+            }
+            self.refill();
+        }
+        self.buf_len -= 1;
+        Some(self.buf[self.buf_len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_map_identity() {
+        let map = OffsetMap::identity(5);
+        assert!(map.is_identity());
+        assert_eq!(map.to_original(3), 3);
+        assert_eq!(map.original_len(), 5);
+    }
+
+    #[test]
+    fn test_offset_map_expansion_and_shrink() {
+        // "aßb" (ß = 2 バイト) → Full で "assb"
+        let (folded, map) = CaseFolding::Full.fold("aßb".as_bytes());
+        assert_eq!(folded, b"assb");
+        assert_eq!(map.to_original(0), 0);
+        assert_eq!(map.to_original(1), 1); // ß の 1 文字目
+        assert_eq!(map.to_original(2), 1); // ß の 2 文字目 → ß 先頭
+        assert_eq!(map.to_original(3), 3);
+        assert_eq!(map.original_len(), 4);
+
+        // ケルビン記号 (3 バイト) → 'k' (1 バイト)
+        let (folded, map) = CaseFolding::Simple.fold("\u{212A}x".as_bytes());
+        assert_eq!(folded, b"kx");
+        assert_eq!(map.to_original(1), 3);
+    }
+
+    #[test]
+    fn test_simple_folding() {
+        let (folded, map) = CaseFolding::Simple.fold("ÄÖÜ ΣΑΣ ς".as_bytes());
+        assert_eq!(folded, "äöü σασ σ".as_bytes());
+        assert!(map.is_identity());
+    }
+
+    #[test]
+    fn test_full_folding_expands() {
+        let (folded, _) = CaseFolding::Full.fold("Straße ﬁx".as_bytes());
+        assert_eq!(folded, b"strasse fix");
+        // Simple は長さを変えない
+        let (folded, _) = CaseFolding::Simple.fold("Straße".as_bytes());
+        assert_eq!(folded, "straße".as_bytes());
+    }
+
+    #[test]
+    fn test_dotted_capital_i() {
+        // CaseFolding.txt: İ は S の対応がなく、F で i + U+0307。ı は対応なし
+        let (folded, _) = CaseFolding::Simple.fold("İstanbul".as_bytes());
+        assert_eq!(folded, "İstanbul".as_bytes());
+        let (folded, _) = CaseFolding::Full.fold("İSTANBUL ı".as_bytes());
+        assert_eq!(folded, "i\u{307}stanbul ı".as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_utf8_passthrough() {
+        let text = [b'A', 0xFF, b'B', 0xC3];
+        let (folded, map) = CaseFolding::Full.fold(&text);
+        assert_eq!(folded, vec![b'a', 0xFF, b'b', 0xC3]);
+        assert!(map.is_identity());
+    }
+
+    #[test]
+    fn test_ascii_folding_keeps_bytes() {
+        let (folded, map) = CaseFolding::Ascii.fold("ÄBC".as_bytes());
+        assert_eq!(folded, "Äbc".as_bytes());
+        assert!(map.is_identity());
+    }
+
+    #[test]
+    fn test_fold_rev_matches_fold() {
+        let patterns: [&[u8]; 6] = [
+            "Straße ﬁx".as_bytes(),
+            "İSTANBUL ΣΑΣ".as_bytes(),
+            &[b'A', 0xFF, b'B', 0xC3],
+            &[0xE3, 0x81, b'Q', 0xE3, 0x81, 0x82],
+            &[0x80, 0x80, 0x80, 0x80, 0x80],
+            b"",
+        ];
+        for folding in [CaseFolding::Simple, CaseFolding::Full] {
+            for pattern in patterns {
+                let mut rev: Vec<u8> = FoldRev::new(pattern, folding).collect();
+                rev.reverse();
+                assert_eq!(rev, folding.fold(pattern).0, "{pattern:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_utf8() {
+        assert_eq!(decode_utf8(b"a"), Some(('a', 1)));
+        assert_eq!(decode_utf8("é".as_bytes()), Some(('é', 2)));
+        assert_eq!(decode_utf8("あ".as_bytes()), Some(('あ', 3)));
+        assert_eq!(decode_utf8(&[0x80]), None);
+        assert_eq!(decode_utf8(&[0xE3, 0x81]), None);
+        assert_eq!(decode_utf8(b""), None);
+    }
+}
@@ -0,0 +1,1211 @@
+//! Segmented Incremental Index (LSM-style)
+//!
+//! **Architecture**:
+//! - Text is appended to an unindexed tail; `seal()` indexes only the tail as
+//!   an immutable segment (O(tail), not O(total)) that owns its text
+//! - Segments are merged by size tier: `fanout` neighbours of the same tier
+//!   become one segment of the next tier, so each byte is re-indexed
+//!   O(log_fanout N) times over the life of the index
+//! - Queries fan out over all segments and add the seam matches that cross
+//!   segment boundaries
+//! - The unindexed tail is scanned with Horspool, so queries always see the
+//!   whole stream, with or without `rebuild()`
+//!
+//! **Seams**: a match crossing boundaries is attributed to the first boundary
+//! it crosses and found by scanning at most `2M - 2` bytes around it, so
+//! every match is reported exactly once.
+//!
+//! **Deletion**: each `append` is a chunk with a `DocId`. `delete` sets a
+//! tombstone bit; matches touching a deleted chunk are filtered out. Merges
+//! drop deleted chunks physically, and positions stay logical (offsets in
+//! the stream as appended), so matches that would only exist across the
+//! removed gap are rejected as well. A gap map (the runs of chunks that are
+//! contiguous in the stream) keeps counts and position lookups fast after
+//! compaction.
+//!
+//! **Retention**: chunks carry a timestamp. `expire_before` hides older
+//! chunks at once and drops whole expired segments from the front, which
+//! frees their text without moving the rest; each segment keeps its time
+//! span so windowed queries skip segments outside the window.
+
+extern crate alloc;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::{Range, RangeBounds};
+
+use crate::bitvec::BitVector;
+use crate::document::DocId;
+use crate::search::{AliceIndex, IndexOptions};
+
+/// Default number of same-tier segments merged together.
+pub const DEFAULT_FANOUT: usize = 4;
+
+/// 不変セグメント（ストリームの連続区間のテキストとインデックス）。
+///
+/// `Arc` で共有するため、インデックスの複製は変更されたセグメントだけを持つ。
+struct Segment {
+    /// Physical offset of the segment
+    start: usize,
+    text: Vec<u8>,
+    index: AliceIndex,
+    /// Oldest / newest chunk timestamp in the segment
+    min_time: u64,
+    max_time: u64,
+}
+
+impl Segment {
+    /// Index `text`, stored at physical offset `start`; `chunks` are the
+    /// chunks lying in it.
+    fn build(start: usize, text: Vec<u8>, chunks: &[Chunk], options: &IndexOptions) -> Self {
+        let min_time = chunks.iter().map(|c| c.timestamp).min().unwrap_or(0);
+        let max_time = chunks.iter().map(|c| c.timestamp).max().unwrap_or(0);
+        Self {
+            start,
+            index: AliceIndex::build_with_options(&text, options),
+            text,
+            min_time,
+            max_time,
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    #[inline]
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+
+    /// Whether any chunk of the segment may fall in `window`.
+    #[inline]
+    fn overlaps<W: RangeBounds<u64>>(&self, window: &W) -> bool {
+        use core::ops::Bound::{Excluded, Included, Unbounded};
+        let after_start = match window.start_bound() {
+            Included(&t) => self.max_time >= t,
+            Excluded(&t) => self.max_time > t,
+            Unbounded => true,
+        };
+        let before_end = match window.end_bound() {
+            Included(&t) => self.min_time <= t,
+            Excluded(&t) => self.min_time < t,
+            Unbounded => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// 1 回の `append` で追加されたテキスト（空の追加は記録しない）。
+#[derive(Clone, Copy, Debug)]
+struct Chunk {
+    id: DocId,
+    /// Physical offset (changes when earlier chunks are compacted away)
+    start: usize,
+    /// Offset in the stream as appended (never changes)
+    logical: usize,
+    len: usize,
+    timestamp: u64,
+}
+
+impl Chunk {
+    #[inline]
+    const fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    #[inline]
+    const fn logical_end(&self) -> usize {
+        self.logical + self.len
+    }
+}
+
+/// インクリメンタルインデックスビルダー。
+///
+/// テキストを逐次追加し、`rebuild()` で未索引の末尾をセグメントとして封印する。
+/// セグメントはサイズ階層ごとにマージされ、検索は全セグメントに分散して
+/// セグメント境界を跨ぐマッチも 1 回ずつ報告する。
+/// 未索引の末尾も走査するため、検索は常にバッファ全体を反映する。
+///
+/// `clone` はセグメントを共有するため O(セグメント数 + チャンク数 + 末尾長)。
+#[derive(Clone)]
+pub struct IncrementalIndex {
+    /// 未索引の末尾（`seal` でセグメントになる）。
+    tail: Vec<u8>,
+    /// 末尾の物理オフセット（索引済み部分の終端）。
+    tail_start: usize,
+    /// 保持している先頭バイトの物理オフセット（期限切れで前進する）。
+    origin: usize,
+    /// セグメントのビルドオプション。
+    options: IndexOptions,
+    /// 同一階層のセグメントをいくつ溜めたらマージするか。
+    fanout: usize,
+    /// 先頭から順に並んだ索引済みセグメント（複製間で共有）。
+    segments: Vec<Arc<Segment>>,
+    /// `rebuild()` / `seal()` が呼ばれたか。
+    built: bool,
+    /// バッファ順に並んだ追加チャンク。
+    chunks: Vec<Chunk>,
+    /// チャンク ID ごとの削除フラグ。
+    tombstones: BitVector,
+    /// 削除済み・期限切れでバッファに残っているチャンク数。
+    hidden: usize,
+    /// これまでに追加された総バイト数（論理長）。
+    logical_len: usize,
+    /// 保持期間の下限。これより古いチャンクは検索対象外。
+    cutoff: u64,
+    /// ギャップマップ: ストリーム上で連続するチャンク列ごとの
+    /// `(物理オフセット, 論理位置)`。圧縮で取り除いた区間の後で新しい列が始まる。
+    runs: Vec<(usize, usize)>,
+    /// 直前に追加したチャンクのタイムスタンプ。
+    last_timestamp: u64,
+}
+
+impl IncrementalIndex {
+    /// 新規作成。
+    #[must_use]
+    pub fn new(sample_step: usize) -> Self {
+        Self::with_options(&IndexOptions::with_sample_step(sample_step), DEFAULT_FANOUT)
+    }
+
+    /// ビルドオプションとマージのファンアウト（2 以上に丸める）を指定して作成する。
+    #[must_use]
+    pub fn with_options(options: &IndexOptions, fanout: usize) -> Self {
+        Self {
+            tail: Vec::new(),
+            tail_start: 0,
+            origin: 0,
+            options: IndexOptions {
+                sample_step: options.sample_step.max(1),
+                ..*options
+            },
+            fanout: fanout.max(2),
+            segments: Vec::new(),
+            built: false,
+            chunks: Vec::new(),
+            tombstones: BitVector::new(),
+            hidden: 0,
+            logical_len: 0,
+            cutoff: 0,
+            runs: Vec::new(),
+            last_timestamp: 0,
+        }
+    }
+
+    /// テキストを追加し、削除に使うチャンク ID を返す。
+    ///
+    /// タイムスタンプは直前のチャンクと同じになる（初回は 0）。
+    /// 追加したテキストは即座に検索対象になる。`rebuild()` までは
+    /// 未索引部分として線形走査されるため、定期的に封印すること。
+    pub fn append(&mut self, text: &[u8]) -> DocId {
+        self.append_at(text, self.last_timestamp)
+    }
+
+    /// タイムスタンプ付きでテキストを追加し、チャンク ID を返す。
+    ///
+    /// 単位は任意（UNIX 秒など）。保持期間・時間窓の判定にのみ使う。
+    pub fn append_at(&mut self, text: &[u8], timestamp: u64) -> DocId {
+        let id = self.tombstones.len();
+        self.tombstones.push(false);
+        self.last_timestamp = timestamp;
+        if !text.is_empty() {
+            let chunk = Chunk {
+                id,
+                start: self.end(),
+                logical: self.logical_len,
+                len: text.len(),
+                timestamp,
+            };
+            if self.is_hidden(&chunk) {
+                self.hidden += 1;
+            }
+            if self
+                .chunks
+                .last()
+                .is_none_or(|c| c.logical_end() != chunk.logical)
+            {
+                self.runs.push((chunk.start, chunk.logical));
+            }
+            self.chunks.push(chunk);
+            self.tail.extend_from_slice(text);
+            self.logical_len += text.len();
+        }
+        id
+    }
+
+    /// チャンクを削除（トゥームストーン化）する。新たに削除した場合 `true`。
+    ///
+    /// 検索結果からは即座に除外され、次のマージ (`rebuild` / `compact`)
+    /// でバッファとセグメントから物理的に取り除かれる。
+    pub fn delete(&mut self, id: DocId) -> bool {
+        if id >= self.tombstones.len() || self.tombstones.get(id) {
+            return false;
+        }
+        if let Ok(i) = self.chunks.binary_search_by_key(&id, |c| c.id) {
+            if !self.is_hidden(&self.chunks[i]) {
+                self.hidden += 1;
+            }
+        }
+        self.tombstones.set(id, true);
+        true
+    }
+
+    /// チャンクが削除済みか。
+    #[must_use]
+    pub fn is_deleted(&self, id: DocId) -> bool {
+        id < self.tombstones.len() && self.tombstones.get(id)
+    }
+
+    /// `cutoff` より古いチャンクを期限切れにし、取り除いたバイト数を返す。
+    ///
+    /// 期限切れチャンクは即座に検索対象外になる。全チャンクが期限切れの
+    /// 先頭セグメントはその場で丸ごと破棄し、残りは次のマージで取り除く。
+    pub fn expire_before(&mut self, cutoff: u64) -> usize {
+        self.cutoff = self.cutoff.max(cutoff);
+        let expired = self
+            .segments
+            .iter()
+            .take_while(|s| s.max_time < self.cutoff)
+            .count();
+        let dropped = if expired == 0 {
+            0
+        } else {
+            // Segments own their text: dropping them moves no bytes
+            let bytes = self.segments[expired - 1].end() - self.origin;
+            self.segments.drain(..expired);
+            self.origin = self.segments.first().map_or(self.tail_start, |s| s.start);
+            let gone = self.chunks.partition_point(|c| c.start < self.origin);
+            self.chunks.drain(..gone);
+            self.runs.clear();
+            self.extend_runs(0);
+            bytes
+        };
+        self.hidden = self.chunks.iter().filter(|c| self.is_hidden(c)).count();
+        dropped
+    }
+
+    /// 全セグメントを 1 つにマージし、削除済み・期限切れチャンクをすべて取り除く。
+    pub fn compact(&mut self) {
+        self.seal();
+        if self.segments.len() > 1 || self.hidden > 0 {
+            self.merge_tail(self.segments.len());
+        }
+    }
+
+    /// 未索引の末尾を封印し、必要なマージをすべて行う。
+    ///
+    /// マージは連鎖することがあり、最悪 O(全体) かかる。検索と同じスレッドで
+    /// 呼ぶ場合は `seal` と `merge_step` に分けるか、`LiveIndex` に任せて
+    /// バックグラウンドで実行する。
+    pub fn rebuild(&mut self) {
+        self.seal();
+        while self.merge_step() {}
+    }
+
+    /// 未索引の末尾を新しいセグメントとして封印する（マージは行わない）。O(末尾長)
+    pub fn seal(&mut self) {
+        if !self.tail.is_empty() {
+            let start = self.tail_start;
+            let ci = self.chunks.partition_point(|c| c.start < start);
+            let text = core::mem::take(&mut self.tail);
+            self.tail_start += text.len();
+            let segment = Segment::build(start, text, &self.chunks[ci..], &self.options);
+            self.segments.push(Arc::new(segment));
+        }
+        self.built = true;
+    }
+
+    /// 保留中のマージを 1 回行う。マージしたら `true`。
+    ///
+    /// 呼び出し側の都合の良いタイミングでマージを分割実行できる。
+    pub fn merge_step(&mut self) -> bool {
+        let Some(k) = self.merge_candidate() else {
+            return false;
+        };
+        self.merge_tail(k);
+        true
+    }
+
+    /// Merge the last `k` segments into one, dropping deleted / expired
+    /// chunks from the merged range and the pending tail behind it.
+    fn merge_tail(&mut self, k: usize) {
+        let first = self.segments.len() - k;
+        let start = self
+            .segments
+            .get(first)
+            .map_or(self.tail_start, |s| s.start);
+
+        // Segment boundaries are chunk boundaries: chunks from `ci` on lie
+        // entirely in the merged range or the pending tail.
+        let ci = self.chunks.partition_point(|c| c.start < start);
+        let mut merged = Vec::new();
+        let mut tail = Vec::new();
+        let mut merged_chunks = ci;
+        let mut kept = ci;
+        for i in ci..self.chunks.len() {
+            let chunk = self.chunks[i];
+            if self.is_hidden(&chunk) {
+                continue;
+            }
+            let bytes = self.chunk_bytes(&chunk);
+            let write = start + merged.len() + tail.len();
+            if chunk.start < self.tail_start {
+                merged.extend_from_slice(bytes);
+                merged_chunks = kept + 1;
+            } else {
+                tail.extend_from_slice(bytes);
+            }
+            self.chunks[kept] = Chunk {
+                start: write,
+                ..chunk
+            };
+            kept += 1;
+        }
+        self.segments.truncate(first);
+        self.tail_start = start + merged.len();
+        self.tail = tail;
+        self.chunks.truncate(kept);
+        self.runs
+            .truncate(self.runs.partition_point(|r| r.0 < start));
+        self.extend_runs(ci);
+        self.hidden = self.chunks[..ci]
+            .iter()
+            .filter(|c| self.is_hidden(c))
+            .count();
+
+        if !merged.is_empty() {
+            let merged = Segment::build(
+                start,
+                merged,
+                &self.chunks[ci..merged_chunks],
+                &self.options,
+            );
+            self.segments.push(Arc::new(merged));
+        }
+    }
+
+    /// Add the runs starting at `chunks[from..]` (the runs before them are
+    /// already in place).
+    fn extend_runs(&mut self, from: usize) {
+        for i in from..self.chunks.len() {
+            let chunk = self.chunks[i];
+            if i == 0 || self.chunks[i - 1].logical_end() != chunk.logical {
+                self.runs.push((chunk.start, chunk.logical));
+            }
+        }
+    }
+
+    /// The bytes of a retained chunk (in one segment or in the tail).
+    fn chunk_bytes(&self, chunk: &Chunk) -> &[u8] {
+        let (text, start) = match self.segments.get(self.segment_at(chunk.start)) {
+            Some(segment) => (&segment.text, segment.start),
+            None => (&self.tail, self.tail_start),
+        };
+        &text[chunk.start - start..chunk.end() - start]
+    }
+
+    /// Index of the segment containing physical offset `pos`
+    /// (`segments.len()` in the tail).
+    #[inline]
+    fn segment_at(&self, pos: usize) -> usize {
+        self.segments.partition_point(|s| s.end() <= pos)
+    }
+
+    /// Append the retained bytes at physical offsets `range` to `out`.
+    fn copy_range(&self, range: Range<usize>, out: &mut Vec<u8>) {
+        for segment in &self.segments[self.segment_at(range.start)..] {
+            if segment.start >= range.end {
+                return;
+            }
+            let from = range.start.max(segment.start) - segment.start;
+            let to = range.end.min(segment.end()) - segment.start;
+            out.extend_from_slice(&segment.text[from..to]);
+        }
+        if range.end > self.tail_start {
+            let from = range.start.max(self.tail_start) - self.tail_start;
+            out.extend_from_slice(&self.tail[from..range.end - self.tail_start]);
+        }
+    }
+
+    /// Physical offset just past the retained bytes.
+    #[inline]
+    fn end(&self) -> usize {
+        self.tail_start + self.tail.len()
+    }
+
+    /// マージ対象となる末尾セグメント数。
+    ///
+    /// Keeps tiers non-increasing from oldest to newest, and merges the last
+    /// `fanout` segments once they share a tier.
+    fn merge_candidate(&self) -> Option<usize> {
+        let n = self.segments.len();
+        let tier = |i: usize| self.tier(self.segments[i].len());
+        if n >= 2 && tier(n - 1) > tier(n - 2) {
+            return Some(2);
+        }
+        if n >= self.fanout {
+            let t = tier(n - self.fanout);
+            if (n - self.fanout..n).all(|i| tier(i) == t) {
+                return Some(self.fanout);
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn tier(&self, len: usize) -> u32 {
+        len.max(1).ilog(self.fanout)
+    }
+
+    /// パターンの出現回数（削除済み・期限切れチャンクを除くバッファ全体）。
+    ///
+    /// 削除済みチャンクが残っている間は、各マッチの位置を検証するため
+    /// O(occ × `sample_step`) になる。圧縮で生じたギャップは 1 つあたり
+    /// O(M) の走査で、ギャップを跨ぐマッチを差し引く。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        if pattern.is_empty() {
+            return self.live_len() + 1;
+        }
+        if self.hidden > 0 {
+            return self
+                .raw_matches(pattern, |_| true)
+                .filter_map(|p| self.resolve(p, pattern.len()))
+                .count();
+        }
+        let inside: usize = self.segments.iter().map(|s| s.index.count(pattern)).sum();
+        inside + self.seam_matches(pattern, |_| true).count() + self.tail_matches(pattern).count()
+            - self.gap_matches(pattern)
+    }
+
+    /// Matches that span a gap of the gap map (bytes never adjacent in the
+    /// stream), each counted at the first gap it spans.
+    fn gap_matches(&self, pattern: &[u8]) -> usize {
+        let m = pattern.len();
+        let mut window = Vec::new();
+        let mut total = 0;
+        for pair in self.runs.windows(2) {
+            let (prev, gap) = (pair[0].0, pair[1].0);
+            let from = prev.max((gap + 1).saturating_sub(m));
+            window.clear();
+            self.copy_range(from..self.end().min(gap + m - 1), &mut window);
+            total += window
+                .windows(m)
+                .take(gap - from)
+                .filter(|w| *w == pattern)
+                .count();
+        }
+        total
+    }
+
+    /// パターンが存在するか（削除済み・期限切れチャンクを除くバッファ全体）。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        if pattern.is_empty() {
+            return true;
+        }
+        self.raw_matches(pattern, |_| true)
+            .any(|p| self.resolve(p, pattern.len()).is_some())
+    }
+
+    /// 全出現位置（追加順のストリーム上の論理バイトオフセット）。順序は不定。
+    ///
+    /// 空パターンは `AliceIndex` と同様に全位置（末尾を含む）にマッチする。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        if pattern.is_empty() {
+            let mut positions: Vec<usize> = self
+                .chunks
+                .iter()
+                .filter(|c| !self.is_hidden(c))
+                .flat_map(|c| c.logical..c.logical_end())
+                .collect();
+            positions.push(self.logical_len);
+            return positions;
+        }
+        self.raw_matches(pattern, |_| true)
+            .filter_map(|p| self.resolve(p, pattern.len()))
+            .collect()
+    }
+
+    /// 時間窓内のチャンクから始まるマッチの数。
+    ///
+    /// 窓と重ならないセグメントは検索しない。空パターンは `count` と同じく
+    /// 各バイト位置と末尾にマッチし、末尾は最後の追加のタイムスタンプを持つ
+    /// （`..` なら `count(b"")` と一致する）。
+    #[must_use]
+    pub fn count_in_window<W: RangeBounds<u64>>(&self, pattern: &[u8], window: W) -> usize {
+        if pattern.is_empty() {
+            let inside: usize = self
+                .chunks
+                .iter()
+                .filter(|c| !self.is_hidden(c) && window.contains(&c.timestamp))
+                .map(|c| c.len)
+                .sum();
+            return inside + usize::from(window.contains(&self.last_timestamp));
+        }
+        self.timed_matches(pattern, &window).count()
+    }
+
+    /// 時間窓内のマッチを `(論理位置, チャンクのタイムスタンプ)` で返す。順序は不定。
+    ///
+    /// `..` を渡すと保持期間内の全マッチをタイムスタンプ付きで返す。
+    #[must_use]
+    pub fn locate_in_window<W: RangeBounds<u64>>(
+        &self,
+        pattern: &[u8],
+        window: W,
+    ) -> Vec<(usize, u64)> {
+        self.timed_matches(pattern, &window).collect()
+    }
+
+    fn timed_matches<'a, W: RangeBounds<u64>>(
+        &'a self,
+        pattern: &'a [u8],
+        window: &'a W,
+    ) -> impl Iterator<Item = (usize, u64)> + 'a {
+        let m = pattern.len();
+        let matches = (m > 0).then(|| self.raw_matches(pattern, move |s| s.overlaps(window)));
+        // The empty pattern: every position of the in-window chunks, and the
+        // end of the stream at the time of the last append
+        let positions = (m == 0).then(|| {
+            self.chunks
+                .iter()
+                .filter(move |c| !self.is_hidden(c) && window.contains(&c.timestamp))
+                .flat_map(|c| (c.logical..c.logical_end()).map(move |p| (p, c.timestamp)))
+                .chain(
+                    window
+                        .contains(&self.last_timestamp)
+                        .then_some((self.logical_len, self.last_timestamp)),
+                )
+        });
+        matches
+            .into_iter()
+            .flatten()
+            .filter_map(move |p| self.resolve_chunk(p, m))
+            .filter(move |(_, chunk)| window.contains(&chunk.timestamp))
+            .map(|(pos, chunk)| (pos, chunk.timestamp))
+            .chain(positions.into_iter().flatten())
+    }
+
+    /// Physical offsets of all matches, before tombstone / gap filtering.
+    ///
+    /// `keep` prunes segments (and the seams they start) from the search.
+    fn raw_matches<'a, F>(&'a self, pattern: &'a [u8], keep: F) -> impl Iterator<Item = usize> + 'a
+    where
+        F: Fn(&Segment) -> bool + Copy + 'a,
+    {
+        self.segments
+            .iter()
+            .filter(move |s| keep(s))
+            .flat_map(move |s| s.index.locate(pattern).map(move |p| p + s.start))
+            .chain(self.seam_matches(pattern, keep))
+            .chain(self.tail_matches(pattern))
+    }
+
+    #[inline]
+    fn is_hidden(&self, chunk: &Chunk) -> bool {
+        chunk.timestamp < self.cutoff || self.tombstones.get(chunk.id)
+    }
+
+    /// Logical offset of a match at physical offset `pos`, or `None` if it
+    /// touches a hidden chunk or spans a gap left by compaction.
+    fn resolve(&self, pos: usize, pattern_len: usize) -> Option<usize> {
+        if self.hidden == 0 {
+            // Gap map: O(log runs) instead of walking the chunks
+            let r = self.runs.partition_point(|r| r.0 <= pos) - 1;
+            let (start, logical) = self.runs[r];
+            let spans_gap = self
+                .runs
+                .get(r + 1)
+                .is_some_and(|next| next.0 < pos + pattern_len);
+            return (!spans_gap).then_some(logical + (pos - start));
+        }
+        self.resolve_chunk(pos, pattern_len)
+            .map(|(logical, _)| logical)
+    }
+
+    /// `resolve` that also returns the chunk the match starts in.
+    fn resolve_chunk(&self, pos: usize, pattern_len: usize) -> Option<(usize, &Chunk)> {
+        let i = self.chunks.partition_point(|c| c.start <= pos) - 1;
+        let first = &self.chunks[i];
+        let mut prev = first;
+        if self.is_hidden(prev) {
+            return None;
+        }
+        for next in &self.chunks[i + 1..] {
+            if prev.end() >= pos + pattern_len {
+                break;
+            }
+            if self.is_hidden(next) || next.logical != prev.logical_end() {
+                return None;
+            }
+            prev = next;
+        }
+        Some((first.logical + (pos - first.start), first))
+    }
+
+    /// Matches crossing the end of a segment (into the next one or the tail).
+    ///
+    /// A match starting at `s` is attributed to the first boundary after `s`,
+    /// i.e. the end of the segment containing `s`, and found in a window of
+    /// at most `2M - 2` bytes around it.
+    fn seam_matches<'a, F>(&'a self, pattern: &'a [u8], keep: F) -> impl Iterator<Item = usize> + 'a
+    where
+        F: Fn(&Segment) -> bool + 'a,
+    {
+        let m = pattern.len();
+        self.segments
+            .iter()
+            .filter(move |s| keep(s))
+            .flat_map(move |segment| {
+                let boundary = segment.end();
+                let from = segment.start.max((boundary + 1).saturating_sub(m));
+                let mut window = Vec::new();
+                self.copy_range(from..self.end().min(boundary + m - 1), &mut window);
+                (from..boundary)
+                    .filter(move |&s| window.get(s - from..s - from + m) == Some(pattern))
+            })
+    }
+
+    /// Matches starting in the unindexed tail (Horspool).
+    fn tail_matches<'a>(&'a self, pattern: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        Horspool::new(&self.tail, pattern).map(move |p| p + self.tail_start)
+    }
+
+    /// 索引済みの長さ（バッファ先頭から）。
+    #[must_use]
+    pub const fn indexed_len(&self) -> usize {
+        self.tail_start - self.origin
+    }
+
+    /// 蓄積テキストの長さ（物理、圧縮・期限切れで取り除いた分を除く）。
+    #[must_use]
+    pub const fn buffer_len(&self) -> usize {
+        self.tail_start + self.tail.len() - self.origin
+    }
+
+    /// これまでに追加された総バイト数（論理長、削除分を含む）。
+    #[must_use]
+    pub const fn logical_len(&self) -> usize {
+        self.logical_len
+    }
+
+    /// 削除されておらず保持期間内のチャンクの総バイト数。
+    #[must_use]
+    pub fn live_len(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|c| !self.is_hidden(c))
+            .map(|c| c.len)
+            .sum()
+    }
+
+    /// 削除済み・期限切れで物理的に残っているチャンク数。
+    #[must_use]
+    pub const fn deleted_count(&self) -> usize {
+        self.hidden
+    }
+
+    /// 保持期間の下限（`expire_before` で設定した最大値）。
+    #[must_use]
+    pub const fn retention_cutoff(&self) -> u64 {
+        self.cutoff
+    }
+
+    /// 未索引（走査対象）の末尾の長さ。
+    #[must_use]
+    pub fn pending_len(&self) -> usize {
+        self.tail.len()
+    }
+
+    /// セグメント数。
+    #[must_use]
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// 各セグメントのバッファ上の範囲（古い順）。
+    pub fn segment_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.segments
+            .iter()
+            .map(|s| s.start - self.origin..s.end() - self.origin)
+    }
+
+    /// インデックスが構築済みか。
+    #[must_use]
+    pub const fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// インデックスの概算バイトサイズ（バッファを除く）。
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        self.segments.iter().map(|s| s.index.size_bytes()).sum()
+    }
+
+    /// 蓄積テキストをクリアしてインデックスをリセットする。
+    pub fn clear(&mut self) {
+        self.tail.clear();
+        self.tail_start = 0;
+        self.origin = 0;
+        self.segments.clear();
+        self.built = false;
+        self.chunks.clear();
+        self.tombstones = BitVector::new();
+        self.hidden = 0;
+        self.logical_len = 0;
+        self.cutoff = 0;
+        self.runs.clear();
+        self.last_timestamp = 0;
+    }
+}
+
+/// Boyer-Moore-Horspool substring search (overlapping matches).
+///
+/// The shift table lives on the stack, so a scan does not allocate.
+struct Horspool<'a> {
+    haystack: &'a [u8],
+    needle: &'a [u8],
+    shift: [usize; 256],
+    pos: usize,
+}
+
+impl<'a> Horspool<'a> {
+    fn new(haystack: &'a [u8], needle: &'a [u8]) -> Self {
+        let m = needle.len();
+        let mut shift = [m; 256];
+        for (i, &b) in needle.iter().enumerate().take(m.saturating_sub(1)) {
+            shift[b as usize] = m - 1 - i;
+        }
+        Self {
+            haystack,
+            needle,
+            shift,
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for Horspool<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let m = self.needle.len();
+        if m == 0 {
+            return None;
+        }
+        while self.pos + m <= self.haystack.len() {
+            let pos = self.pos;
+            let last = self.haystack[pos + m - 1];
+            self.pos += self.shift[last as usize];
+            if last == self.needle[m - 1] && self.haystack[pos..pos + m - 1] == self.needle[..m - 1]
+            {
+                return Some(pos);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 素朴な部分文字列検索（検証用）
+    fn naive(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        if pattern.len() > text.len() {
+            return Vec::new();
+        }
+        (0..=text.len() - pattern.len())
+            .filter(|&i| &text[i..i + pattern.len()] == pattern)
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_empty() {
+        let index = IncrementalIndex::new(4);
+        assert!(!index.is_built());
+        assert_eq!(index.buffer_len(), 0);
+        assert_eq!(index.count(b"a"), 0);
+        assert!(!index.contains(b"a"));
+        assert!(index.locate_all(b"a").is_empty());
+    }
+
+    #[test]
+    fn test_incremental_append_and_rebuild() {
+        let mut index = IncrementalIndex::new(4);
+        index.append(b"hello ");
+        index.append(b"world");
+        assert_eq!(index.buffer_len(), 11);
+        assert!(!index.is_built());
+
+        index.rebuild();
+        assert!(index.is_built());
+        assert_eq!(index.count(b"hello"), 1);
+        assert!(index.contains(b"world"));
+    }
+
+    #[test]
+    fn test_incremental_rebuild_reflects_new_data() {
+        let mut index = IncrementalIndex::new(1);
+        index.append(b"aaa");
+        index.rebuild();
+        assert_eq!(index.count(b"a"), 3);
+
+        index.append(b"aa");
+        // rebuild前でも未索引の末尾が走査される
+        assert_eq!(index.count(b"a"), 5);
+        assert_eq!(index.pending_len(), 2);
+
+        index.rebuild();
+        assert_eq!(index.count(b"a"), 5);
+        assert_eq!(index.pending_len(), 0);
+    }
+
+    #[test]
+    fn test_incremental_locate() {
+        let mut index = IncrementalIndex::new(1);
+        index.append(b"abcabc");
+        index.rebuild();
+
+        let mut positions = index.locate_all(b"abc");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_incremental_clear() {
+        let mut index = IncrementalIndex::new(4);
+        index.append(b"hello");
+        index.rebuild();
+        assert!(index.is_built());
+
+        index.clear();
+        assert!(!index.is_built());
+        assert_eq!(index.buffer_len(), 0);
+        assert_eq!(index.count(b"hello"), 0);
+    }
+
+    #[test]
+    fn test_incremental_sample_step_zero_clamped() {
+        let index = IncrementalIndex::new(0);
+        // sample_step=0 は 1 にクランプされる
+        // 正常に動作することを検証
+        assert!(!index.is_built());
+    }
+
+    #[test]
+    fn test_rebuild_indexes_only_tail() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(2), 8);
+        index.append(b"first chunk ");
+        index.rebuild();
+        index.append(b"second chunk");
+        index.rebuild();
+
+        let ranges: Vec<_> = index.segment_ranges().collect();
+        assert_eq!(ranges, vec![0..12, 12..24]);
+        assert_eq!(index.indexed_len(), 24);
+        assert_eq!(index.count(b"chunk"), 2);
+    }
+
+    #[test]
+    fn test_seam_matches_found_once() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 16);
+        // "needle" をセグメント境界で分割して追加
+        for part in [&b"xxnee"[..], b"dl", b"e needle ne", b"e", b"dle"] {
+            index.append(part);
+            index.rebuild();
+        }
+        assert_eq!(index.segment_count(), 5);
+
+        let text = b"xxneedle needle needle";
+        let mut positions = index.locate_all(b"needle");
+        positions.sort_unstable();
+        assert_eq!(positions, naive(text, b"needle"));
+        assert_eq!(index.count(b"needle"), 3);
+        assert!(index.contains(b"eedle ne"));
+    }
+
+    #[test]
+    fn test_tiered_merge_bounds_segment_count() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(4), 2);
+        let mut text = Vec::new();
+        for i in 0..200u32 {
+            let chunk = alloc::format!("log line {i} status=ok;");
+            index.append(chunk.as_bytes());
+            text.extend_from_slice(chunk.as_bytes());
+            index.rebuild();
+        }
+        // fanout 2 の階層マージでセグメント数は対数オーダーに収まる
+        assert!(index.segment_count() <= 16, "{}", index.segment_count());
+        let ranges: Vec<_> = index.segment_ranges().collect();
+        assert!(ranges.windows(2).all(|w| w[0].end == w[1].start));
+
+        for pattern in [&b"status=ok;log"[..], b"line 1", b"9 s", b";"] {
+            let mut positions = index.locate_all(pattern);
+            positions.sort_unstable();
+            assert_eq!(positions, naive(&text, pattern));
+        }
+    }
+
+    #[test]
+    fn test_seal_then_merge_step() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 2);
+        for part in [&b"ab"[..], b"ab", b"ab"] {
+            index.append(part);
+            index.seal();
+        }
+        assert_eq!(index.segment_count(), 3);
+        assert_eq!(index.count(b"ba"), 2);
+
+        while index.merge_step() {}
+        assert!(index.segment_count() < 3);
+        assert_eq!(index.count(b"ba"), 2);
+        assert_eq!(index.count(b"abab"), 2);
+    }
+
+    #[test]
+    fn test_fresh_reads_without_rebuild() {
+        let mut index = IncrementalIndex::new(2);
+        index.append(b"abcab");
+        assert!(!index.is_built());
+        assert_eq!(index.count(b"ab"), 2);
+        assert!(index.contains(b"cab"));
+        assert_eq!(index.locate_all(b"b"), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_fresh_reads_across_indexed_seam() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 16);
+        index.append(b"the quick brown fox jumps");
+        index.rebuild();
+        index.append(b" over the lazy dog, the fox");
+        index.rebuild();
+        index.append(b" jumps again; the f");
+        index.append(b"ox");
+
+        let text = b"the quick brown fox jumps over the lazy dog, the fox jumps again; the fox";
+        for pattern in [
+            &b"fox"[..],
+            b"the",
+            b"fox jumps",
+            b"s over the",
+            b"; the fox",
+            b"o",
+        ] {
+            let mut positions = index.locate_all(pattern);
+            positions.sort_unstable();
+            assert_eq!(positions, naive(text, pattern), "{pattern:?}");
+            assert_eq!(index.count(pattern), positions.len());
+        }
+        assert!(!index.contains(b"cat"));
+        assert_eq!(index.count(b""), text.len() + 1);
+    }
+
+    #[test]
+    fn test_horspool_overlapping() {
+        let hits: Vec<_> = Horspool::new(b"aaaaa", b"aa").collect();
+        assert_eq!(hits, vec![0, 1, 2, 3]);
+        let hits: Vec<_> = Horspool::new(b"abcabdabc", b"abc").collect();
+        assert_eq!(hits, vec![0, 6]);
+        assert_eq!(Horspool::new(b"ab", b"abc").count(), 0);
+    }
+
+    #[test]
+    fn test_delete_chunk_filters_matches() {
+        let mut index = IncrementalIndex::new(1);
+        let a = index.append(b"secret=abc;");
+        let b = index.append(b"name=alice;");
+        index.rebuild();
+        let c = index.append(b"secret=xyz;");
+        assert_eq!((a, b, c), (0, 1, 2));
+        assert_eq!(index.count(b"secret"), 2);
+
+        assert!(index.delete(a));
+        assert!(!index.delete(a));
+        assert!(!index.delete(99));
+        assert!(index.is_deleted(a));
+        assert_eq!(index.count(b"secret"), 1);
+        assert_eq!(index.locate_all(b"secret"), vec![22]);
+        // 削除チャンクに掛かるマッチも除外
+        assert!(!index.contains(b";name"));
+        assert!(index.contains(b";secret"));
+        assert_eq!(index.live_len(), 22);
+    }
+
+    #[test]
+    fn test_compaction_drops_deleted_bytes() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(2), 8);
+        index.append(b"ab");
+        let mid = index.append(b"XX");
+        index.append(b"cd");
+        index.rebuild();
+        index.delete(mid);
+        assert_eq!(index.deleted_count(), 1);
+
+        index.compact();
+        assert_eq!(index.segment_count(), 1);
+        assert_eq!(index.buffer_len(), 4);
+        assert_eq!(index.logical_len(), 6);
+        assert_eq!(index.deleted_count(), 0);
+
+        // 位置は論理オフセットのまま
+        assert_eq!(index.locate_all(b"cd"), vec![4]);
+        assert_eq!(index.count(b"X"), 0);
+        // 削除で隣接した "b" + "c" は元のストリームには存在しない
+        assert_eq!(index.count(b"bc"), 0);
+        assert_eq!(index.count(b"abcd"), 0);
+
+        index.append(b"ef");
+        let mut positions = index.locate_all(b"def");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![5]);
+    }
+
+    #[test]
+    fn test_counts_stay_exact_after_compaction() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(3), 2);
+        // 削除区間を 0 で埋めたストリームが期待値（パターンに 0 は含まれない）
+        let mut stream = Vec::new();
+        for i in 0..40u32 {
+            let chunk = alloc::format!("ab{}a", i % 3);
+            let id = index.append(chunk.as_bytes());
+            if i % 4 == 1 {
+                index.delete(id);
+                stream.extend(core::iter::repeat_n(0, chunk.len()));
+            } else {
+                stream.extend_from_slice(chunk.as_bytes());
+            }
+            if i % 8 == 7 {
+                index.compact();
+            }
+        }
+        index.compact();
+        index.append(b"ab0a");
+        stream.extend_from_slice(b"ab0a");
+        assert_eq!(index.deleted_count(), 0);
+        assert!(index.buffer_len() < index.logical_len());
+
+        // "0aab2" / "1aab0" は削除区間を跨いで物理的にだけ隣接する
+        for pattern in [&b"a"[..], b"aab", b"0aab1", b"ab2aab0", b"0aab2", b"1aab0"] {
+            let expected = naive(&stream, pattern);
+            let mut positions = index.locate_all(pattern);
+            positions.sort_unstable();
+            assert_eq!(positions, expected, "{pattern:?}");
+            assert_eq!(index.count(pattern), expected.len(), "{pattern:?}");
+            assert_eq!(index.contains(pattern), !expected.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_merge_compacts_deleted_chunks() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 2);
+        let mut ids = Vec::new();
+        for i in 0..8u8 {
+            ids.push(index.append(&[b'a' + i; 4]));
+            if i == 2 {
+                index.delete(ids[1]);
+            }
+            index.rebuild();
+        }
+        // マージで "bbbb" は物理的に消える
+        assert_eq!(index.buffer_len(), 28);
+        assert_eq!(index.count(b"b"), 0);
+        assert_eq!(index.locate_all(b"cccc"), vec![8]);
+        let mut positions = index.locate_all(b"hh");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![28, 29, 30]);
+    }
+
+    #[test]
+    fn test_expire_drops_whole_segments() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 8);
+        index.append_at(b"old error;", 100);
+        index.seal();
+        index.append_at(b"mid error;", 200);
+        index.seal();
+        index.append_at(b"new error;", 300);
+
+        // 期限切れのセグメントだけが丸ごと破棄される
+        assert_eq!(index.expire_before(150), 10);
+        assert_eq!(index.segment_count(), 1);
+        assert_eq!(index.buffer_len(), 20);
+        assert_eq!(index.count(b"error"), 2);
+        // 位置は論理オフセットのまま
+        let mut positions = index.locate_all(b"error");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![14, 24]);
+        assert!(!index.contains(b"old"));
+    }
+
+    #[test]
+    fn test_expire_keeps_seams_and_tail() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 8);
+        for (part, ts) in [(&b"old needle ne"[..], 1), (b"edle ne", 2), (b"edl", 3)] {
+            index.append_at(part, ts);
+            index.seal();
+        }
+        index.append_at(b"e needle", 4);
+
+        assert_eq!(index.expire_before(2), 13);
+        // 残りのセグメントは物理位置を保ったまま先頭基準で報告される
+        let ranges: Vec<_> = index.segment_ranges().collect();
+        assert_eq!(ranges, vec![0..7, 7..10]);
+        assert_eq!(index.pending_len(), 8);
+        let text = b"old needle needle needle needle";
+        let mut positions = index.locate_all(b"needle");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![18, 25]);
+        assert_eq!(naive(text, b"needle")[2..], positions[..]);
+        assert_eq!(index.count(b"needle"), 2);
+        assert_eq!(index.count(b""), index.live_len() + 1);
+    }
+
+    #[test]
+    fn test_expire_hides_chunks_inside_segment() {
+        let mut index = IncrementalIndex::new(1);
+        index.append_at(b"abc;", 1);
+        index.append_at(b"abd;", 5);
+        index.rebuild();
+
+        // セグメント内の一部だけが期限切れ: 即座に非表示、次のマージで除去
+        assert_eq!(index.expire_before(3), 0);
+        assert_eq!(index.retention_cutoff(), 3);
+        assert_eq!(index.count(b"ab"), 1);
+        assert_eq!(index.locate_all(b"ab"), vec![4]);
+        assert_eq!(index.live_len(), 4);
+        index.compact();
+        assert_eq!(index.buffer_len(), 4);
+        assert_eq!(index.locate_all(b"ab"), vec![4]);
+        // 期限切れ以前のタイムスタンプで追加しても検索対象外
+        index.append_at(b"abx", 2);
+        assert_eq!(index.count(b"ab"), 1);
+    }
+
+    #[test]
+    fn test_window_queries_return_timestamps() {
+        let mut index = IncrementalIndex::with_options(&IndexOptions::with_sample_step(1), 8);
+        for (i, ts) in [10u64, 20, 30, 40].into_iter().enumerate() {
+            index.append_at(b"GET /", ts);
+            index.append(if i % 2 == 0 { b"a;" } else { b"b;" });
+            index.seal();
+        }
+        assert_eq!(index.count_in_window(b"GET", ..), 4);
+        assert_eq!(index.count_in_window(b"GET", 20..40), 2);
+        assert_eq!(index.count_in_window(b"GET", 20..=40), 3);
+        // 空パターンは count と同じ意味（各位置と末尾）
+        assert_eq!(index.count_in_window(b"", ..), index.count(b""));
+        assert_eq!(index.count_in_window(b"", 20..40), 14);
+        assert_eq!(index.locate_in_window(b"", 40..).len(), 8);
+
+        let mut hits = index.locate_in_window(b"GET", 25..);
+        hits.sort_unstable();
+        assert_eq!(hits, vec![(14, 30), (21, 40)]);
+        // `append` は直前のタイムスタンプを引き継ぐ
+        assert_eq!(index.locate_in_window(b"b;", ..15), vec![]);
+        assert_eq!(index.locate_in_window(b"b;", 20..=20), vec![(12, 20)]);
+        // 時間窓を跨ぐマッチは開始チャンクのタイムスタンプで判定する
+        assert_eq!(index.locate_in_window(b";GET", 10..11), vec![(6, 10)]);
+    }
+}
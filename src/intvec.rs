@@ -0,0 +1,227 @@
+//! Fixed-Width Packed Integer Vector
+//!
+//! **Layout**: value `i` occupies bits `[i × width, (i + 1) × width)` of a
+//! little-endian `u64` stream. A value straddles at most two words, so
+//! `get` is O(1): one or two loads, a shift and a mask.
+//!
+//! Used for the SA / ISA samples, whose values are below the text length
+//! and need ⌈log₂ N⌉ bits instead of a full `usize`.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Vector of unsigned integers packed at a fixed bit width.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntVector {
+    words: Vec<u64>,
+    /// Bits per value (0..=64)
+    width: usize,
+    len: usize,
+}
+
+impl IntVector {
+    /// Empty vector of `width`-bit values.
+    ///
+    /// # Panics
+    /// Panics if `width` exceeds 64.
+    #[must_use]
+    pub const fn new(width: usize) -> Self {
+        assert!(width <= 64, "IntVector: width above 64 bits");
+        Self {
+            words: Vec::new(),
+            width,
+            len: 0,
+        }
+    }
+
+    /// Empty vector with room for `capacity` values.
+    #[must_use]
+    pub fn with_capacity(width: usize, capacity: usize) -> Self {
+        let mut v = Self::new(width);
+        v.words.reserve(Self::words_for(capacity, width));
+        v
+    }
+
+    /// `len` zeros of `width` bits (fill with `set`).
+    #[must_use]
+    pub fn zeroed(width: usize, len: usize) -> Self {
+        let mut v = Self::new(width);
+        v.words.resize(Self::words_for(len, width), 0);
+        v.len = len;
+        v
+    }
+
+    /// Pack `values` with the smallest width that holds `max_value`.
+    ///
+    /// # Panics
+    /// Panics if a value exceeds `max_value`'s width.
+    #[must_use]
+    pub fn from_slice(values: &[usize], max_value: usize) -> Self {
+        let mut v = Self::with_capacity(Self::width_for(max_value), values.len());
+        for &value in values {
+            v.push(value);
+        }
+        v
+    }
+
+    /// Bits needed to store every value in `0..=max_value` (0 for 0).
+    #[inline]
+    #[must_use]
+    pub const fn width_for(max_value: usize) -> usize {
+        (usize::BITS - max_value.leading_zeros()) as usize
+    }
+
+    /// Heap bytes of `len` values of `width` bits.
+    #[inline]
+    #[must_use]
+    pub const fn bytes_for(len: usize, width: usize) -> usize {
+        Self::words_for(len, width) * 8
+    }
+
+    #[inline]
+    const fn words_for(len: usize, width: usize) -> usize {
+        (len * width).div_ceil(64)
+    }
+
+    /// Append a value.
+    ///
+    /// # Panics
+    /// Panics if `value` does not fit in `width` bits.
+    #[inline]
+    pub fn push(&mut self, value: usize) {
+        self.len += 1;
+        self.words.resize(Self::words_for(self.len, self.width), 0);
+        self.set(self.len - 1, value);
+    }
+
+    /// Overwrite the value at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index >= len()` or `value` does not fit in `width` bits.
+    #[inline]
+    pub fn set(&mut self, index: usize, value: usize) {
+        assert!(index < self.len, "IntVector: index out of bounds");
+        let value = value as u64;
+        assert!(
+            value & !self.mask() == 0,
+            "IntVector: value {value} wider than {} bits",
+            self.width
+        );
+        if self.width == 0 {
+            return;
+        }
+        let bit = index * self.width;
+        let (word, offset) = (bit / 64, bit % 64);
+        self.words[word] = self.words[word] & !(self.mask() << offset) | value << offset;
+        if offset + self.width > 64 {
+            let spill = 64 - offset;
+            self.words[word + 1] = self.words[word + 1] & !(self.mask() >> spill) | value >> spill;
+        }
+    }
+
+    #[inline]
+    const fn mask(&self) -> u64 {
+        if self.width == 64 {
+            u64::MAX
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    /// Value at `index`. O(1)
+    ///
+    /// # Panics
+    /// Panics if `index >= len()`.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: usize) -> usize {
+        assert!(index < self.len, "IntVector: index out of bounds");
+        if self.width == 0 {
+            return 0;
+        }
+        let bit = index * self.width;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.words[word] >> offset;
+        if offset + self.width > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        (value & self.mask()) as usize
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bits per value.
+    #[inline]
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Heap bytes of the packed words.
+    #[inline]
+    #[must_use]
+    pub const fn size_bytes(&self) -> usize {
+        Self::bytes_for(self.len, self.width)
+    }
+
+    /// Iterate over the values in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = usize> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_every_width() {
+        for width in 0..=64usize {
+            let max = if width == 64 {
+                u64::MAX
+            } else {
+                (1u64 << width) - 1
+            } as usize;
+            let values: Vec<usize> = (0..200usize)
+                .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) & max)
+                .collect();
+            let v = IntVector::from_slice(&values, max);
+            assert_eq!(v.width(), width);
+            assert_eq!(v.len(), values.len());
+            assert_eq!(v.iter().collect::<Vec<_>>(), values, "width {width}");
+            assert_eq!(v.size_bytes(), (200 * width).div_ceil(64) * 8);
+        }
+    }
+
+    #[test]
+    fn test_set_overwrites_in_place() {
+        let mut v = IntVector::zeroed(13, 40);
+        for i in 0..40 {
+            v.set(i, 8191);
+        }
+        for i in (0..40).step_by(3) {
+            v.set(i, i * 7);
+        }
+        let expected: Vec<usize> = (0..40)
+            .map(|i| if i % 3 == 0 { i * 7 } else { 8191 })
+            .collect();
+        assert_eq!(v.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "wider than")]
+    fn test_push_rejects_wide_value() {
+        let mut v = IntVector::new(3);
+        v.push(8);
+    }
+}
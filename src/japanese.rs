@@ -0,0 +1,395 @@
+//! Japanese Text Folding Search
+//!
+//! **Folding rules** (each can be toggled):
+//! - Width: full-width ASCII → ASCII, `U+3000` → space,
+//!   half-width katakana → full-width (with `ﾞ`/`ﾟ` composed: `ｶﾞ` → `ガ`)
+//! - Kana: hiragana → katakana, combining/spacing voicing marks composed
+//! - Marks: iteration marks (`ゝゞヽヾ々`) expanded to the repeated char,
+//!   long-vowel variants after kana (`ｰ`, `〜`, `―`, ...) → `ー`
+//!
+//! Built on the same `OffsetMap` mechanism as `CaseInsensitiveIndex`, so
+//! positions are byte offsets in the original text, always on a UTF-8
+//! character boundary.
+//!
+//! Marks fold by the char before them, which a pattern starting with a mark
+//! does not have: such a mark stays as typed and does not match where the
+//! text folded it (see `JapaneseIndex`).
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::fold::{decode_utf8, fold_units, push_char, FoldedLocateIter, OffsetMap};
+use crate::search::AliceIndex;
+
+/// 日本語折り畳みの設定。既定ではすべて有効。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JapaneseFolding {
+    /// 全角/半角の統一。
+    pub width: bool,
+    /// ひらがな → カタカナの統一。
+    pub kana: bool,
+    /// 踊り字の展開と長音記号の統一。
+    pub marks: bool,
+}
+
+impl Default for JapaneseFolding {
+    fn default() -> Self {
+        Self {
+            width: true,
+            kana: true,
+            marks: true,
+        }
+    }
+}
+
+/// Half-width katakana block `U+FF61..=U+FF9F` → full-width equivalents.
+const HALFWIDTH_KANA: [u16; 63] = [
+    0x3002, 0x300C, 0x300D, 0x3001, 0x30FB, 0x30F2, 0x30A1,
+    0x30A3, // ｡｢｣､･ｦｧｨ
+    0x30A5, 0x30A7, 0x30A9, 0x30E3, 0x30E5, 0x30E7, 0x30C3,
+    0x30FC, // ｩｪｫｬｭｮｯｰ
+    0x30A2, 0x30A4, 0x30A6, 0x30A8, 0x30AA, 0x30AB, 0x30AD,
+    0x30AF, // ｱｲｳｴｵｶｷｸ
+    0x30B1, 0x30B3, 0x30B5, 0x30B7, 0x30B9, 0x30BB, 0x30BD,
+    0x30BF, // ｹｺｻｼｽｾｿﾀ
+    0x30C1, 0x30C4, 0x30C6, 0x30C8, 0x30CA, 0x30CB, 0x30CC,
+    0x30CD, // ﾁﾂﾃﾄﾅﾆﾇﾈ
+    0x30CE, 0x30CF, 0x30D2, 0x30D5, 0x30D8, 0x30DB, 0x30DE,
+    0x30DF, // ﾉﾊﾋﾌﾍﾎﾏﾐ
+    0x30E0, 0x30E1, 0x30E2, 0x30E4, 0x30E6, 0x30E8, 0x30E9,
+    0x30EA, // ﾑﾒﾓﾔﾕﾖﾗﾘ
+    0x30EB, 0x30EC, 0x30ED, 0x30EF, 0x30F3, 0x309B, 0x309C, // ﾙﾚﾛﾜﾝﾞﾟ
+];
+
+impl JapaneseFolding {
+    /// テキストを折り畳み、`OffsetMap` と共に返す。
+    ///
+    /// 不正な UTF-8 バイトはそのまま通過する。
+    #[must_use]
+    pub fn fold(self, text: &[u8]) -> (Vec<u8>, OffsetMap) {
+        // Last folded char, for iteration marks and long-vowel context
+        let mut prev: Option<char> = None;
+        fold_units(text, |rest, out| {
+            let Some((c, mut consumed)) = decode_utf8(rest) else {
+                out.push(rest[0]);
+                prev = None;
+                return 1;
+            };
+            let mut c = self.fold_char(c);
+
+            // Compose a following voicing mark into the base kana
+            if self.width || self.kana {
+                if let Some((mark, len)) = decode_utf8(&rest[consumed..]) {
+                    if let Some(composed) = compose_voicing(c, mark) {
+                        c = composed;
+                        consumed += len;
+                    }
+                }
+            }
+
+            if self.marks {
+                c = match (c, prev) {
+                    ('\u{30FD}', Some(p)) if is_katakana(p) => unvoice(p),
+                    ('\u{309D}', Some(p)) if is_hiragana(p) => unvoice(p),
+                    ('\u{30FE}' | '\u{309E}', Some(p)) if is_kana(p) => {
+                        compose_voicing(unvoice(p), '\u{3099}').unwrap_or(p)
+                    }
+                    ('\u{3005}', Some(p)) if is_ideograph(p) => p,
+                    (m, Some(p)) if is_long_vowel_variant(m) && is_kana(p) => '\u{30FC}',
+                    (c, _) => c,
+                };
+            }
+
+            push_char(out, c);
+            prev = Some(c);
+            consumed
+        })
+    }
+
+    /// Context-free per-char folding (width + kana).
+    fn fold_char(self, c: char) -> char {
+        let mut c = c;
+        if self.width {
+            c = match c {
+                '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+                '\u{3000}' => ' ',
+                '\u{FF61}'..='\u{FF9F}' => {
+                    let mapped = HALFWIDTH_KANA[(c as u32 - 0xFF61) as usize];
+                    char::from_u32(u32::from(mapped)).unwrap_or(c)
+                }
+                _ => c,
+            };
+        }
+        if self.kana {
+            c = match c {
+                // ぁ..ゖ, ゝ, ゞ → ァ..ヶ, ヽ, ヾ
+                '\u{3041}'..='\u{3096}' | '\u{309D}' | '\u{309E}' => {
+                    char::from_u32(c as u32 + 0x60).unwrap_or(c)
+                }
+                _ => c,
+            };
+        }
+        c
+    }
+}
+
+#[inline]
+const fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}')
+}
+
+#[inline]
+const fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A1}'..='\u{30FA}')
+}
+
+#[inline]
+const fn is_kana(c: char) -> bool {
+    is_hiragana(c) || is_katakana(c)
+}
+
+#[inline]
+const fn is_ideograph(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// Chars commonly typed in place of `ー` after kana.
+#[inline]
+const fn is_long_vowel_variant(c: char) -> bool {
+    matches!(
+        c,
+        '\u{30FC}' | '\u{FF70}' | '\u{2010}'
+            ..='\u{2015}' | '\u{2212}' | '\u{2500}' | '\u{301C}' | '\u{FF5E}' | '-' | '~'
+    )
+}
+
+/// `base` + voicing mark → voiced / semi-voiced kana (hiragana or katakana).
+fn compose_voicing(base: char, mark: char) -> Option<char> {
+    let semi = match mark {
+        '\u{3099}' | '\u{309B}' | '\u{FF9E}' => false,
+        '\u{309A}' | '\u{309C}' | '\u{FF9F}' => true,
+        _ => return None,
+    };
+    let (kata, hira_offset) = if is_hiragana(base) {
+        (base as u32 + 0x60, 0x60)
+    } else {
+        (base as u32, 0)
+    };
+    let voiced = match (kata, semi) {
+        // カ..ト (voiceable members only)
+        (0x30AB..=0x30C2, false) if kata % 2 == 1 => kata + 1,
+        (0x30C4 | 0x30C6 | 0x30C8, false) => kata + 1,
+        // ハ ヒ フ ヘ ホ
+        (0x30CF | 0x30D2 | 0x30D5 | 0x30D8 | 0x30DB, false) => kata + 1,
+        (0x30CF | 0x30D2 | 0x30D5 | 0x30D8 | 0x30DB, true) => kata + 2,
+        // ウ → ヴ, ワヰヱヲ → ヷヸヹヺ (katakana only)
+        (0x30A6, false) if hira_offset == 0 => 0x30F4,
+        (0x30EF..=0x30F2, false) if hira_offset == 0 => kata + 8,
+        _ => return None,
+    };
+    char::from_u32(voiced - hira_offset)
+}
+
+/// Voiced / semi-voiced kana → base kana (identity for other chars).
+fn unvoice(c: char) -> char {
+    let code = c as u32;
+    for base in [code.wrapping_sub(1), code.wrapping_sub(2)] {
+        if let Some(b) = char::from_u32(base) {
+            if compose_voicing(b, '\u{3099}') == Some(c)
+                || compose_voicing(b, '\u{309A}') == Some(c)
+            {
+                return b;
+            }
+        }
+    }
+    match c {
+        '\u{30F4}' => '\u{30A6}',
+        '\u{30F7}'..='\u{30FA}' => char::from_u32(code - 8).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// 日本語の表記ゆれを折り畳んだインデックス。
+///
+/// 位置は元テキストのバイトオフセット（UTF-8 文字境界）で返す。
+/// UTF-8 として不正なパターンは常に 0 件。
+///
+/// 踊り字と長音の異体字（`ゝ` `ヽ` `々` `〜` など）は直前の文字に応じて
+/// 折り畳むため、パターン先頭のものは折り畳まれず、テキスト中で展開・統一
+/// された箇所にはマッチしない（`ゝろ` は `こゝろ` にマッチしない）。
+/// 直前の文字からパターンに含めるか、折り畳み後の文字で検索すること。
+pub struct JapaneseIndex {
+    inner: AliceIndex,
+    folding: JapaneseFolding,
+    offsets: OffsetMap,
+}
+
+impl JapaneseIndex {
+    /// 既定の折り畳み（すべて有効）でインデックスを構築する。
+    #[must_use]
+    pub fn build(text: &[u8], sample_step: usize) -> Self {
+        Self::build_with_folding(text, sample_step, JapaneseFolding::default())
+    }
+
+    /// 折り畳み設定を指定してインデックスを構築する。
+    #[must_use]
+    pub fn build_with_folding(text: &[u8], sample_step: usize, folding: JapaneseFolding) -> Self {
+        let (folded, offsets) = folding.fold(text);
+        Self {
+            inner: AliceIndex::build(&folded, sample_step),
+            folding,
+            offsets,
+        }
+    }
+
+    /// パターンの出現回数。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        let range = self.search_range(pattern);
+        range.end - range.start
+    }
+
+    /// パターンが存在するか。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.count(pattern) > 0
+    }
+
+    /// 出現位置（元テキストのバイトオフセット）を返すイテレータ。
+    #[must_use]
+    pub fn locate(&self, pattern: &[u8]) -> FoldedLocateIter<'_> {
+        let range = self.search_range(pattern);
+        FoldedLocateIter::new(self.inner.locate_range(range), &self.offsets)
+    }
+
+    /// 全出現位置を Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        self.locate(pattern).collect()
+    }
+
+    /// 元テキストの長さ。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.offsets.original_len()
+    }
+
+    /// 折り畳み設定。
+    #[must_use]
+    pub const fn folding(&self) -> JapaneseFolding {
+        self.folding
+    }
+
+    /// インデックスの概算バイトサイズ（位置対応表を含む）。
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        self.inner.size_bytes() + self.offsets.size_bytes()
+    }
+
+    /// A valid UTF-8 pattern folds to whole chars, so every match in the
+    /// folded text starts and ends on a char boundary.
+    fn search_range(&self, pattern: &[u8]) -> core::ops::Range<usize> {
+        if pattern.is_empty() || core::str::from_utf8(pattern).is_err() {
+            return 0..0;
+        }
+        self.inner.search_range(&self.folding.fold(pattern).0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(text: &str) -> String {
+        String::from_utf8(JapaneseFolding::default().fold(text.as_bytes()).0).unwrap()
+    }
+
+    #[test]
+    fn test_width_folding() {
+        assert_eq!(fold("ＡＢＣ１２３"), "ABC123");
+        assert_eq!(fold("ｶﾀｶﾅ"), "カタカナ");
+        assert_eq!(fold("ｶﾞｷﾞﾊﾟ"), "ガギパ");
+        assert_eq!(fold("東京\u{3000}駅"), "東京 駅");
+    }
+
+    #[test]
+    fn test_kana_unification() {
+        assert_eq!(fold("ひらがな"), "ヒラガナ");
+        assert_eq!(fold("か\u{3099}"), "ガ");
+        assert_eq!(fold("は\u{309C}"), "パ");
+        assert_eq!(fold("ウ\u{309B}"), "ヴ");
+    }
+
+    #[test]
+    fn test_iteration_and_long_vowel_marks() {
+        assert_eq!(fold("いすゞ"), "イスズ");
+        assert_eq!(fold("こゝろ"), "ココロ");
+        assert_eq!(fold("時々"), "時時");
+        assert_eq!(fold("コーヒー"), "コーヒー");
+        assert_eq!(fold("コ〜ヒｰ"), "コーヒー");
+        assert_eq!(fold("ﾗｰﾒﾝ"), "ラーメン");
+        // 仮名の後以外のハイフンはそのまま
+        assert_eq!(fold("A-B"), "A-B");
+    }
+
+    #[test]
+    fn test_search_across_variants() {
+        let text = "ｶﾀｶﾅ と かたかな と カタカナ".as_bytes();
+        let index = JapaneseIndex::build(text, 1);
+
+        assert_eq!(index.count("カタカナ".as_bytes()), 3);
+        assert_eq!(index.count("かたかな".as_bytes()), 3);
+        assert_eq!(index.count("ｶﾀｶﾅ".as_bytes()), 3);
+
+        let mut positions = index.locate_all("かたかな".as_bytes());
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 17, 34]);
+        assert_eq!(index.text_len(), text.len());
+    }
+
+    #[test]
+    fn test_offsets_with_composed_halfwidth() {
+        // "ｶﾞ" (6 バイト) → "ガ" (3 バイト) の後ろの位置も元テキストに戻る
+        let text = "ｶﾞｽ ガス".as_bytes();
+        let index = JapaneseIndex::build(text, 2);
+        let mut positions = index.locate_all("ス".as_bytes());
+        positions.sort_unstable();
+        assert_eq!(positions, vec![6, 13]);
+        assert_eq!(index.count("がす".as_bytes()), 2);
+    }
+
+    #[test]
+    fn test_non_boundary_pattern_rejected() {
+        let index = JapaneseIndex::build("カタカナ".as_bytes(), 1);
+        let bytes = "タ".as_bytes();
+        assert_eq!(index.count(&bytes[1..]), 0);
+        assert_eq!(index.count(&bytes[..2]), 0);
+        assert!(index.locate_all(&bytes[1..]).is_empty());
+    }
+
+    #[test]
+    fn test_leading_mark_has_no_context() {
+        // パターン先頭の記号は直前の文字がないため折り畳まれない（既知の制限）
+        let index = JapaneseIndex::build("こゝろ と コ〜ヒー".as_bytes(), 1);
+        assert_eq!(index.count("ゝろ".as_bytes()), 0);
+        assert_eq!(index.count("〜ヒー".as_bytes()), 0);
+        // 直前の文字を含めるか、折り畳み後の文字なら見つかる
+        assert_eq!(index.count("こゝろ".as_bytes()), 1);
+        assert_eq!(index.count("コ〜ヒー".as_bytes()), 1);
+        assert_eq!(index.locate_all("ーヒー".as_bytes()), vec![17]);
+        assert_eq!(index.count("コロ".as_bytes()), 1);
+    }
+
+    #[test]
+    fn test_folding_toggles() {
+        let folding = JapaneseFolding {
+            width: true,
+            kana: false,
+            marks: false,
+        };
+        let index = JapaneseIndex::build_with_folding("ｶﾀｶﾅ ひらがな".as_bytes(), 1, folding);
+        assert_eq!(index.count("カタカナ".as_bytes()), 1);
+        assert_eq!(index.count("ヒラガナ".as_bytes()), 0);
+        assert_eq!(index.folding(), folding);
+    }
+}
@@ -57,13 +57,37 @@
 extern crate alloc;
 
 pub mod bitvec;
+pub mod build;
 pub mod bwt;
+mod casefold;
+pub mod dna;
+pub mod document;
+#[cfg(feature = "std")]
+pub mod external;
 #[cfg(feature = "ffi")]
 pub mod ffi;
+pub mod fold;
+pub mod incremental;
+pub mod intvec;
+pub mod japanese;
+mod parallel;
 pub mod search;
+pub mod token;
+pub mod utf8;
 pub mod wavelet;
 
-pub use search::{AliceIndex, CaseInsensitiveIndex, IncrementalIndex};
+pub use build::{
+    BuildConfig, BuildError, BuildObserver, BuildPhase, IndexBuilder, SampleTuning, SamplingPlan,
+    SamplingStrategy,
+};
+pub use dna::DnaIndex;
+pub use document::DocumentIndex;
+pub use fold::{CaseFolding, OffsetMap};
+pub use incremental::IncrementalIndex;
+pub use japanese::JapaneseIndex;
+pub use search::{AliceIndex, Alphabet, BuildStats, CaseInsensitiveIndex, IndexOptions};
+pub use token::TokenIndex;
+pub use utf8::Utf8Index;
 
 #[cfg(feature = "analytics")]
 pub mod analytics_bridge;
@@ -71,6 +95,12 @@ pub mod analytics_bridge;
 pub mod cache_bridge;
 #[cfg(feature = "db")]
 pub mod db_bridge;
+#[cfg(feature = "std")]
+pub mod live;
+#[cfg(feature = "normalize")]
+pub mod normalize;
+#[cfg(feature = "normalize")]
+pub use normalize::{Normalization, NormalizedIndex};
 #[cfg(feature = "text")]
 pub mod text_bridge;
 
@@ -0,0 +1,384 @@
+//! Live Index with Background Indexing (Snapshot Isolation)
+//!
+//! **Architecture**:
+//! - `append` only queues the bytes and wakes the worker thread
+//! - The worker owns a segmented `IncrementalIndex`: it indexes the queued
+//!   bytes as a new segment (O(new bytes), not O(total)) and publishes a
+//!   clone by swapping an `Arc<Snapshot>`. The clone shares every segment,
+//!   so a publish copies no text
+//! - Tier merges run on the worker one `merge_step` at a time: one after
+//!   every seal, even under a steady stream of appends, so the segment count
+//!   (and the fan-out of every query) stays bounded, then more while idle
+//!   until none remain. Each merge is published like an append; neither
+//!   queries nor `append` ever wait for a merge cascade
+//! - Readers clone the current `Arc` under a short read lock and query it
+//!   without holding any lock; in-flight queries keep the old snapshot
+//!   alive until they drop it
+//!
+//! Appends that arrive during a build are coalesced into the next build.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::JoinHandle;
+
+use crate::incremental::{IncrementalIndex, DEFAULT_FANOUT};
+use crate::search::IndexOptions;
+
+/// 公開済みのインデックスのスナップショット（不変）。
+pub struct Snapshot {
+    index: IncrementalIndex,
+    generation: u64,
+    covered_len: usize,
+}
+
+impl Snapshot {
+    /// スナップショットのインデックス。
+    #[must_use]
+    pub const fn index(&self) -> &IncrementalIndex {
+        &self.index
+    }
+
+    /// 公開の世代（初期の空インデックスは 0）。
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// このスナップショットが索引している、追加されたテキストの長さ。
+    #[must_use]
+    pub const fn covered_len(&self) -> usize {
+        self.covered_len
+    }
+}
+
+/// Worker-visible state guarded by one mutex.
+struct State {
+    /// Appended bytes not yet taken by the worker
+    pending: Vec<u8>,
+    /// Total bytes appended
+    appended: usize,
+    /// Set on drop, or by the worker when it exits
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Wakes the worker when data is appended or on shutdown
+    work: Condvar,
+    /// Signalled (under `state`) after each publish and when the worker exits
+    published: Condvar,
+    snapshot: RwLock<Arc<Snapshot>>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn current(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.snapshot.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// バックグラウンドで索引付けするインデックスハンドル。
+///
+/// `append` はブロックせずワーカースレッドに索引付けを依頼する。
+/// ワーカーは追加分だけを新しいセグメントとして索引する。
+/// 検索は常に最新の公開済みスナップショットに対して行われ、
+/// 実行中の検索は古いスナップショットのまま完了する。
+pub struct LiveIndex {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl LiveIndex {
+    /// ワーカースレッドを起動して空のインデックスを作成する。
+    #[must_use]
+    pub fn new(options: &IndexOptions) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                pending: Vec::new(),
+                appended: 0,
+                shutdown: false,
+            }),
+            work: Condvar::new(),
+            published: Condvar::new(),
+            snapshot: RwLock::new(Arc::new(Snapshot {
+                index: IncrementalIndex::with_options(options, DEFAULT_FANOUT),
+                generation: 0,
+                covered_len: 0,
+            })),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            let options = *options;
+            std::thread::spawn(move || worker_loop(&shared, &options))
+        };
+        Self {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// テキストを追加し、索引付けを依頼する。検索はブロックしない。
+    pub fn append(&self, text: &[u8]) {
+        if text.is_empty() {
+            return;
+        }
+        let mut state = self.shared.lock();
+        state.pending.extend_from_slice(text);
+        state.appended += text.len();
+        drop(state);
+        self.shared.work.notify_one();
+    }
+
+    /// 現在の公開済みスナップショット。保持している間は解放されない。
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.shared.current()
+    }
+
+    /// 現在の公開済みスナップショットの世代。
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.snapshot().generation()
+    }
+
+    /// 追加済みテキストの総長。
+    #[must_use]
+    pub fn appended_len(&self) -> usize {
+        self.shared.lock().appended
+    }
+
+    /// 公開済みスナップショットが追加済みテキストをすべて含むか。
+    #[must_use]
+    pub fn is_caught_up(&self) -> bool {
+        let appended = self.appended_len();
+        self.snapshot().covered_len() >= appended
+    }
+
+    /// 呼び出し時点までの追加がすべて公開されるまで待ち、そのスナップショットを返す。
+    ///
+    /// ワーカーが終了している（パニックを含む）場合は待たずに現在のスナップショットを返す。
+    #[must_use]
+    pub fn wait_caught_up(&self) -> Arc<Snapshot> {
+        let mut state = self.shared.lock();
+        let target = state.appended;
+        loop {
+            let snapshot = self.shared.current();
+            if snapshot.covered_len() >= target || state.shutdown {
+                return snapshot;
+            }
+            state = self
+                .shared
+                .published
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// パターンの出現回数（現在のスナップショット）。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.snapshot().index().count(pattern)
+    }
+
+    /// パターンが存在するか（現在のスナップショット）。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.snapshot().index().contains(pattern)
+    }
+
+    /// 全出現位置（現在のスナップショット）。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        self.snapshot().index().locate_all(pattern)
+    }
+}
+
+impl Drop for LiveIndex {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.work.notify_one();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Marks the worker as gone when it exits, even by panicking, so
+/// `wait_caught_up` stops waiting for it.
+struct ExitGuard<'a>(&'a Shared);
+
+impl Drop for ExitGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock().shutdown = true;
+        self.0.published.notify_all();
+    }
+}
+
+fn worker_loop(shared: &Shared, options: &IndexOptions) {
+    let _exit = ExitGuard(shared);
+    let mut index = IncrementalIndex::with_options(options, DEFAULT_FANOUT);
+    let mut generation = 0;
+    let mut merging = false;
+    loop {
+        let text = {
+            let mut state = shared.lock();
+            while !state.shutdown && state.pending.is_empty() && !merging {
+                state = shared
+                    .work
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            if state.shutdown {
+                return;
+            }
+            std::mem::take(&mut state.pending)
+        };
+
+        // Index without holding any lock: appends and queries proceed.
+        // New bytes are published before the merge step that follows them
+        if !text.is_empty() {
+            index.append(&text);
+            index.seal();
+            publish(shared, &index, &mut generation);
+        }
+        merging = index.merge_step();
+        if merging {
+            publish(shared, &index, &mut generation);
+        }
+    }
+}
+
+/// Swap in a snapshot of `index` as the next generation.
+fn publish(shared: &Shared, index: &IncrementalIndex, generation: &mut u64) {
+    *generation += 1;
+    let snapshot = Arc::new(Snapshot {
+        index: index.clone(),
+        generation: *generation,
+        covered_len: index.logical_len(),
+    });
+    *shared
+        .snapshot
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = snapshot;
+
+    // Notify under the state lock so waiters cannot miss the publish
+    let _state = shared.lock();
+    shared.published.notify_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_rebuild_catches_up() {
+        let index = LiveIndex::new(&IndexOptions::with_sample_step(2));
+        assert_eq!(index.generation(), 0);
+        assert!(index.is_caught_up());
+
+        index.append(b"hello world, ");
+        index.append(b"hello again");
+        let snapshot = index.wait_caught_up();
+        assert!(index.is_caught_up());
+        assert!(snapshot.generation() >= 1);
+        assert_eq!(snapshot.covered_len(), 24);
+        assert_eq!(index.count(b"hello"), 2);
+        assert_eq!(index.locate_all(b"again"), vec![19]);
+    }
+
+    #[test]
+    fn test_old_snapshot_survives_publish() {
+        let index = LiveIndex::new(&IndexOptions::with_sample_step(1));
+        index.append(b"abc");
+        let old = index.wait_caught_up();
+
+        index.append(b"abc");
+        let new = index.wait_caught_up();
+
+        // 古いスナップショットは新しい公開後も有効
+        assert_eq!(old.index().count(b"abc"), 1);
+        assert_eq!(new.index().count(b"abc"), 2);
+        // 追加分はセグメントとして索引済み（未索引の末尾を残さない）
+        assert_eq!(new.index().pending_len(), 0);
+        assert_eq!(old.index().indexed_len(), 3);
+        assert!(new.generation() > old.generation());
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_appends() {
+        let index = Arc::new(LiveIndex::new(&IndexOptions::with_sample_step(4)));
+        std::thread::scope(|s| {
+            let writer = Arc::clone(&index);
+            s.spawn(move || {
+                for _ in 0..50 {
+                    writer.append(b"x;");
+                }
+            });
+            for _ in 0..2 {
+                let reader = Arc::clone(&index);
+                s.spawn(move || {
+                    for _ in 0..50 {
+                        // 各スナップショットは一貫している
+                        let snapshot = reader.snapshot();
+                        assert_eq!(snapshot.index().count(b"x"), snapshot.covered_len() / 2);
+                    }
+                });
+            }
+        });
+        assert_eq!(index.wait_caught_up().index().count(b"x;"), 50);
+    }
+
+    /// 長さ `len` でマージ待ちがない（各階層 `fanout - 1` 個以下の）ときのセグメント数の上限
+    fn settled_segments(len: usize) -> usize {
+        (DEFAULT_FANOUT - 1) * (len.max(1).ilog(DEFAULT_FANOUT) as usize + 1) + 1
+    }
+
+    /// `done` が成り立つスナップショットを待つ（10 秒で失敗）
+    fn wait_for(index: &LiveIndex, done: impl Fn(&Snapshot) -> bool) -> Arc<Snapshot> {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        loop {
+            let snapshot = index.snapshot();
+            if done(&snapshot) {
+                return snapshot;
+            }
+            assert!(std::time::Instant::now() < deadline, "timed out");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_merges_run_in_background() {
+        let index = LiveIndex::new(&IndexOptions::with_sample_step(2));
+        for i in 0..64u32 {
+            index.append(alloc::format!("line {i};").as_bytes());
+            let _ = index.wait_caught_up();
+        }
+        // バックグラウンドのマージが追いつくまで待つ
+        let snapshot = wait_for(&index, |s| {
+            s.index().segment_count() <= settled_segments(s.covered_len())
+        });
+        assert_eq!(snapshot.index().count(b"line"), 64);
+        assert_eq!(snapshot.index().locate_all(b"line 1;"), vec![7]);
+    }
+
+    #[test]
+    fn test_merges_keep_up_with_steady_appends() {
+        let index = LiveIndex::new(&IndexOptions::with_sample_step(4));
+        for i in 0..400u32 {
+            index.append(alloc::format!("event {i};").as_bytes());
+            if i % 8 == 0 {
+                // 追加が途切れなくても封印ごとにマージが進む
+                let snapshot = index.snapshot();
+                assert!(
+                    snapshot.index().segment_count() <= settled_segments(snapshot.covered_len()),
+                    "{} segments",
+                    snapshot.index().segment_count()
+                );
+            }
+        }
+        let snapshot = index.wait_caught_up();
+        assert_eq!(snapshot.index().count(b"event"), 400);
+    }
+}
@@ -0,0 +1,289 @@
+//! Unicode Normalization and Accent Folding Search
+//!
+//! **Architecture**:
+//! - Text is split into clusters (a starter + following combining marks)
+//! - Each cluster is NFKC-normalized, optionally with diacritics removed
+//! - `OffsetMap` (shared with `CaseInsensitiveIndex`) maps matches back to
+//!   byte offsets in the raw input
+//!
+//! Queries go through the same pipeline, so `cafe` finds `café`, and the
+//! NFC / NFD / compatibility forms of the same text match each other.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::fold::{decode_utf8, fold_units, push_char, CaseFolding, FoldedLocateIter, OffsetMap};
+use crate::search::AliceIndex;
+
+/// 正規化オプション。常に NFKC を適用する。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Normalization {
+    /// ダイアクリティカルマーク（アクセント記号）を除去するか。
+    pub strip_diacritics: bool,
+}
+
+impl Normalization {
+    /// NFKC のみ。
+    pub const NFKC: Self = Self {
+        strip_diacritics: false,
+    };
+
+    /// NFKC + ダイアクリティカルマーク除去 (`café` → `cafe`)。
+    pub const NFKC_STRIP_DIACRITICS: Self = Self {
+        strip_diacritics: true,
+    };
+
+    /// テキストを正規化し、`OffsetMap` と共に返す。
+    ///
+    /// クラスタ単位で正規化するため、クラスタを跨ぐ合成は行われない
+    /// (ハングル字母の合成は例外としてクラスタ内で扱う)。
+    /// 不正な UTF-8 バイトはそのまま通過する。
+    #[must_use]
+    pub fn normalize(self, text: &[u8]) -> (Vec<u8>, OffsetMap) {
+        self.normalize_folded(text, None)
+    }
+
+    /// 正規化の後、各文字に `folding` を適用する（1 パス、`OffsetMap` は 1 つ）。
+    ///
+    /// `IndexBuilder::normalization` と `case_folding` の併用時に使う。
+    #[must_use]
+    pub fn normalize_folded(
+        self,
+        text: &[u8],
+        folding: Option<CaseFolding>,
+    ) -> (Vec<u8>, OffsetMap) {
+        let mut cluster: Vec<char> = Vec::new();
+        fold_units(text, |rest, out| {
+            let Some((first, mut consumed)) = decode_utf8(rest) else {
+                out.push(rest[0]);
+                return 1;
+            };
+            cluster.clear();
+            cluster.push(first);
+            while let Some((c, len)) = decode_utf8(&rest[consumed..]) {
+                if !extends_cluster(c) {
+                    break;
+                }
+                cluster.push(c);
+                consumed += len;
+            }
+            self.normalize_cluster(&cluster, folding, out);
+            consumed
+        })
+    }
+
+    fn normalize_cluster(self, cluster: &[char], folding: Option<CaseFolding>, out: &mut Vec<u8>) {
+        let mut emit = |c: char| match folding {
+            Some(folding) => folding.fold_char(c, &mut |f| push_char(out, f)),
+            None => push_char(out, c),
+        };
+        if self.strip_diacritics {
+            cluster
+                .iter()
+                .copied()
+                .nfkd()
+                .filter(|&c| !is_diacritic(c))
+                .nfc()
+                .for_each(&mut emit);
+        } else {
+            cluster.iter().copied().nfkc().for_each(&mut emit);
+        }
+    }
+}
+
+/// Combining marks and conjoining Hangul vowels/finals stay with the
+/// preceding char so that composition happens inside one cluster.
+#[inline]
+fn extends_cluster(c: char) -> bool {
+    canonical_combining_class(c) != 0 || matches!(c, '\u{1160}'..='\u{11FF}')
+}
+
+/// Combining diacritical marks (Latin / Greek / Cyrillic accents).
+///
+/// Kana voicing marks and Indic vowel signs are deliberately not included.
+#[inline]
+fn is_diacritic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// NFKC 正規化（および任意のアクセント除去）を適用したインデックス。
+///
+/// 位置は `OffsetMap` により元テキストのバイトオフセットで返す。
+pub struct NormalizedIndex {
+    inner: AliceIndex,
+    normalization: Normalization,
+    offsets: OffsetMap,
+}
+
+impl NormalizedIndex {
+    /// 正規化インデックスを構築する。
+    #[must_use]
+    pub fn build(text: &[u8], sample_step: usize, normalization: Normalization) -> Self {
+        let (normalized, offsets) = normalization.normalize(text);
+        Self {
+            inner: AliceIndex::build(&normalized, sample_step),
+            normalization,
+            offsets,
+        }
+    }
+
+    /// パターンの出現回数（パターンも同じ方式で正規化する）。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.inner.count(&self.normalization.normalize(pattern).0)
+    }
+
+    /// パターンが存在するか。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.inner
+            .contains(&self.normalization.normalize(pattern).0)
+    }
+
+    /// 出現位置（元テキストのバイトオフセット）を返すイテレータ。
+    #[must_use]
+    pub fn locate(&self, pattern: &[u8]) -> FoldedLocateIter<'_> {
+        let range = self
+            .inner
+            .search_range(&self.normalization.normalize(pattern).0);
+        FoldedLocateIter::new(self.inner.locate_range(range), &self.offsets)
+    }
+
+    /// 全出現位置を Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        self.locate(pattern).collect()
+    }
+
+    /// 元テキストの長さ。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.offsets.original_len()
+    }
+
+    /// 正規化オプション。
+    #[must_use]
+    pub const fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// インデックスの概算バイトサイズ（位置対応表を含む）。
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        self.inner.size_bytes() + self.offsets.size_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfc_nfd_match_each_other() {
+        // "é" の NFC (U+00E9) と NFD (e + U+0301)
+        let text = "caf\u{e9} and cafe\u{301}".as_bytes();
+        let index = NormalizedIndex::build(text, 1, Normalization::NFKC);
+
+        assert_eq!(index.count("caf\u{e9}".as_bytes()), 2);
+        assert_eq!(index.count("cafe\u{301}".as_bytes()), 2);
+        assert_eq!(index.count(b"cafe"), 0); // アクセント除去なし
+
+        let mut positions = index.locate_all("caf\u{e9}".as_bytes());
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 10]);
+    }
+
+    #[test]
+    fn test_strip_diacritics() {
+        let text = "Caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e, cafe\u{301}".as_bytes();
+        let index = NormalizedIndex::build(text, 2, Normalization::NFKC_STRIP_DIACRITICS);
+
+        assert_eq!(index.count(b"afe"), 2);
+        assert_eq!(index.count("caf\u{e9}".as_bytes()), 1);
+        assert_eq!(index.count(b"creme brulee"), 1);
+        assert_eq!(index.locate_all(b"brulee"), vec![13]);
+        let mut positions = index.locate_all(b"afe");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![1, 24]);
+    }
+
+    #[test]
+    fn test_compatibility_forms() {
+        // 全角英数字・合字・丸数字は NFKC で互換分解される
+        let text = "ＡＢＣ ﬁle \u{2460}".as_bytes();
+        let index = NormalizedIndex::build(text, 1, Normalization::NFKC);
+
+        assert_eq!(index.count(b"ABC"), 1);
+        assert_eq!(index.count(b"file"), 1);
+        assert_eq!(index.count(b"1"), 1);
+        assert_eq!(index.locate_all(b"file"), vec![10]);
+        assert_eq!(index.locate_all(b"1"), vec![16]);
+        assert_eq!(index.text_len(), text.len());
+    }
+
+    #[test]
+    fn test_kana_voicing_marks_kept() {
+        // 濁点はダイアクリティカルマークとして除去しない
+        let text = "か\u{3099}が".as_bytes();
+        let (normalized, _) = Normalization::NFKC_STRIP_DIACRITICS.normalize(text);
+        assert_eq!(normalized, "がが".as_bytes());
+    }
+
+    #[test]
+    fn test_hangul_jamo_compose() {
+        let text = "\u{1100}\u{1161}\u{11A8}".as_bytes();
+        let (normalized, map) = Normalization::NFKC.normalize(text);
+        assert_eq!(normalized, "\u{AC01}".as_bytes());
+        assert_eq!(map.to_original(0), 0);
+    }
+
+    #[test]
+    fn test_builder_option_matches_normalized_index() {
+        let text = "Caf\u{e9} CAFE\u{301} ＣＡＦＥ cafe".as_bytes();
+        let index = crate::build::IndexBuilder::new()
+            .sample_step(2)
+            .normalization(Normalization::NFKC_STRIP_DIACRITICS)
+            .case_folding(CaseFolding::Full)
+            .build(text)
+            .unwrap();
+        assert_eq!(
+            index.config().normalization,
+            Some(Normalization::NFKC_STRIP_DIACRITICS)
+        );
+        // 正規化 → 折り畳みの順で、パターンにも同じ変換をかける
+        assert_eq!(index.count(b"cafe"), 4);
+        assert_eq!(index.count("CAFÉ".as_bytes()), 4);
+        let mut positions = index.locate_all(b"cafe");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 6, 13, 26]);
+        assert_eq!(index.count_batch(&[b"cafe", "ＣＡＦ".as_bytes()]), [4, 4]);
+
+        // 折り畳みなしなら NormalizedIndex と同じ結果
+        let plain = crate::build::IndexBuilder::new()
+            .normalization(Normalization::NFKC)
+            .build(text)
+            .unwrap();
+        let wrapper = NormalizedIndex::build(text, 16, Normalization::NFKC);
+        for pattern in [&b"Caf"[..], b"CAFE", "cafe\u{301}".as_bytes()] {
+            assert_eq!(plain.count(pattern), wrapper.count(pattern));
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8_passthrough() {
+        let text = [b'a', 0xFF, b'b'];
+        let (normalized, map) = Normalization::NFKC.normalize(&text);
+        assert_eq!(normalized, text);
+        assert!(map.is_identity());
+    }
+}
@@ -0,0 +1,129 @@
+//! Scoped-thread helpers for the parallel build
+//!
+//! Work is split into contiguous chunks and run on `std::thread::scope`
+//! threads — no pool, no external runtime. Results come back in chunk
+//! order, so callers can merge them into output identical to a
+//! sequential pass. Without `std` every helper degrades to a sequential
+//! loop over the same chunks.
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Effective worker count for a requested `IndexOptions::threads`.
+///
+/// `0` means all available cores, and larger requests are capped there:
+/// more threads than cores only add spawns. Without `std` the build is
+/// sequential.
+#[must_use]
+pub(crate) fn resolve_threads(requested: usize) -> usize {
+    #[cfg(feature = "std")]
+    {
+        let cores = std::thread::available_parallelism().map_or(1, usize::from);
+        if requested == 0 {
+            return cores;
+        }
+        // Tests keep a few threads even on one core so the chunked paths run
+        let cap = if cfg!(test) { cores.max(8) } else { cores };
+        requested.min(cap)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = requested;
+        1
+    }
+}
+
+/// Smallest chunk worth a thread: a spawn costs about as much as scanning
+/// this many items, so shorter inputs (and the small, deep SA-IS levels)
+/// run inline. Tests lower it so the chunked paths run on small inputs.
+const MIN_CHUNK: usize = if cfg!(test) { 64 } else { 64 * 1024 };
+
+/// Split `0..n` into at most `parts` contiguous ranges of at least
+/// `MIN_CHUNK` items (one range below that) whose interior boundaries are
+/// multiples of `align` (empty `n` yields one empty range).
+#[must_use]
+pub(crate) fn chunk_ranges(n: usize, parts: usize, align: usize) -> Vec<Range<usize>> {
+    let units = n.div_ceil(align).max(1);
+    let parts = parts.min(n / MIN_CHUNK).clamp(1, units);
+    let per = units.div_ceil(parts);
+    (0..parts)
+        .map(|i| ((i * per * align).min(n), ((i + 1) * per * align).min(n)))
+        .filter(|&(start, end)| start < end || n == 0)
+        .map(|(start, end)| start..end)
+        .collect()
+}
+
+/// Apply `f` to every item, one scoped thread per item; results keep the
+/// input order. The first item runs on the calling thread.
+pub(crate) fn scope_map<I, T, F>(items: Vec<I>, f: F) -> Vec<T>
+where
+    I: Send,
+    T: Send,
+    F: Fn(I) -> T + Sync,
+{
+    #[cfg(feature = "std")]
+    {
+        if items.len() > 1 {
+            let f = &f;
+            return std::thread::scope(|scope| {
+                let mut items = items.into_iter();
+                let first = items.next();
+                let handles: Vec<_> = items.map(|item| scope.spawn(move || f(item))).collect();
+                let mut results = Vec::with_capacity(handles.len() + 1);
+                results.extend(first.map(f));
+                results.extend(handles.into_iter().map(|h| {
+                    h.join()
+                        .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+                }));
+                results
+            });
+        }
+    }
+    items.into_iter().map(f).collect()
+}
+
+/// Split `slice` into consecutive mutable pieces of the given lengths.
+pub(crate) fn split_lengths<T>(
+    mut slice: &mut [T],
+    lengths: impl IntoIterator<Item = usize>,
+) -> Vec<&mut [T]> {
+    let mut pieces = Vec::new();
+    for len in lengths {
+        let (head, tail) = slice.split_at_mut(len);
+        pieces.push(head);
+        slice = tail;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_ranges_aligned_and_covering() {
+        let ranges = chunk_ranges(1300, 4, 512);
+        assert_eq!(ranges, vec![0..512, 512..1024, 1024..1300]);
+        assert_eq!(chunk_ranges(200, 3, 1), vec![0..67, 67..134, 134..200]);
+        // 最小チャンク未満はスレッドを使わない
+        assert_eq!(chunk_ranges(130, 8, 1), vec![0..65, 65..130]);
+        assert_eq!(chunk_ranges(10, 3, 1), vec![0..10]);
+        assert_eq!(chunk_ranges(0, 8, 64), vec![0..0]);
+        assert_eq!(chunk_ranges(5, 0, 1), vec![0..5]);
+    }
+
+    #[test]
+    fn test_resolve_threads_is_capped() {
+        let all = resolve_threads(0);
+        assert!(all >= 1);
+        assert_eq!(resolve_threads(usize::MAX), all.max(8));
+        assert_eq!(resolve_threads(1), 1);
+    }
+
+    #[test]
+    fn test_scope_map_keeps_order() {
+        let squares = scope_map((0..6u64).collect(), |x| x * x);
+        assert_eq!(squares, vec![0, 1, 4, 9, 16, 25]);
+    }
+}
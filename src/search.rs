@@ -13,30 +13,302 @@
 //! This is independent of text size N. Mathematical victory.
 
 extern crate alloc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::ops::Range;
 
 use crate::bitvec::BitVector;
-use crate::bwt::{build_c_table, build_suffix_array, SENTINEL};
+use crate::build::{
+    self, BuildConfig, BuildError, BuildObserver, BuildPhase, Progress, SamplingPlan,
+    SamplingStrategy,
+};
+use crate::bwt::{build_c_table, build_suffix_array_metered, PeakMeter, SaIndex, SENTINEL};
+use crate::fold::{CaseFolding, FoldRev, FoldedLocateIter, OffsetMap};
+use crate::intvec::IntVector;
+use crate::parallel;
 use crate::wavelet::WaveletMatrix;
 
+/// `IncrementalIndex` used to live in this module; keep the old path working.
+pub use crate::incremental::IncrementalIndex;
+
+/// Patterns (or occurrence rows) advanced together by the batched queries
+const BATCH_LANES: usize = 32;
+
+/// Dense byte alphabet of an indexed text.
+///
+/// Maps every byte that occurs in the text to an order-preserving code in
+/// `1..=σ`. Code 0 is shared by the sentinel and by bytes absent from the
+/// text, so an absent byte in a pattern short-circuits backward search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    /// `code[b]` = dense code of byte `b` (0 = sentinel / absent)
+    code: [u8; 256],
+    /// `byte[code]` = original byte (inverse of `code`)
+    byte: [u8; 256],
+    /// Number of distinct non-sentinel symbols
+    sigma: usize,
+}
+
+impl Alphabet {
+    /// Detect the bytes used by `text` and assign dense codes.
+    #[must_use]
+    pub fn from_text(text: &[u8]) -> Self {
+        let mut used = [false; 256];
+        for &b in text {
+            used[b as usize] = true;
+        }
+        Self::from_used(&used)
+    }
+
+    /// Dense codes for the bytes flagged in `used`.
+    pub(crate) fn from_used(used: &[bool; 256]) -> Self {
+        let mut code = [0u8; 256];
+        let mut byte = [0u8; 256];
+        let mut sigma = 0;
+        // Byte 0 stays on code 0 together with the sentinel.
+        for b in 1..256 {
+            if used[b] {
+                sigma += 1;
+                code[b] = sigma as u8;
+                byte[sigma] = b as u8;
+            }
+        }
+        Self { code, byte, sigma }
+    }
+
+    /// Identity mapping (no compaction): every byte is its own code.
+    #[must_use]
+    pub fn identity() -> Self {
+        let mut code = [0u8; 256];
+        for (b, c) in code.iter_mut().enumerate() {
+            *c = b as u8;
+        }
+        Self {
+            code,
+            byte: code,
+            sigma: 255,
+        }
+    }
+
+    /// Dense code of byte `b` (0 if `b` does not occur in the text)
+    #[inline(always)]
+    #[must_use]
+    pub const fn code(&self, b: u8) -> u8 {
+        self.code[b as usize]
+    }
+
+    /// Original byte for a dense code
+    #[inline(always)]
+    #[must_use]
+    pub const fn byte(&self, code: u8) -> u8 {
+        self.byte[code as usize]
+    }
+
+    /// Number of distinct symbols (excluding the sentinel)
+    #[inline]
+    #[must_use]
+    pub const fn sigma(&self) -> usize {
+        self.sigma
+    }
+}
+
+/// Build options shared by `AliceIndex` and the folded indexes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IndexOptions {
+    /// SA sampling interval (trade-off: lower = faster locate, more memory)
+    pub sample_step: usize,
+    /// Remap the used bytes to dense codes (fewer Wavelet Matrix layers)
+    pub compact_alphabet: bool,
+    /// Build threads: 1 = sequential, 0 = all cores (needs `std`); capped
+    /// at the number of cores.
+    /// The index is bit-identical for every thread count.
+    pub threads: usize,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            sample_step: 16,
+            compact_alphabet: true,
+            threads: 1,
+        }
+    }
+}
+
+impl IndexOptions {
+    /// Default options with the given SA sampling interval
+    #[must_use]
+    pub fn with_sample_step(sample_step: usize) -> Self {
+        Self {
+            sample_step,
+            ..Self::default()
+        }
+    }
+}
+
+/// Memory report of [`AliceIndex::build_with_stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Peak bytes of the build buffers (suffix array, SA-IS scratch and
+    /// bucket tables, BWT, Wavelet Matrix buffers, samples); the input text
+    /// is not counted. Modeled, not measured: each buffer is booked at its
+    /// allocated size, small allocations and allocator overhead are left out
+    pub peak_bytes: usize,
+    /// Bytes per suffix array entry (4 when the text is below 4 GiB)
+    pub sa_entry_bytes: usize,
+    /// `size_bytes()` of the finished index
+    pub index_bytes: usize,
+    /// Chosen sample step and the size / locate cost predicted for it
+    pub plan: SamplingPlan,
+}
+
+/// Sample every SA entry whose text position is a multiple of `step`.
+///
+/// Chunks are 512-aligned so their packed bit words concatenate into the
+/// same `BitVector` a sequential pass would push.
+fn sample_suffix_array<I: SaIndex>(
+    sa: &[I],
+    step: usize,
+    threads: usize,
+) -> (IntVector, BitVector) {
+    let ranges = parallel::chunk_ranges(sa.len(), threads, 512);
+    let parts = parallel::scope_map(ranges, |r| {
+        let chunk = &sa[r];
+        let mut samples = Vec::with_capacity(chunk.len() / step + 1);
+        let mut words = vec![0u64; chunk.len().div_ceil(64)];
+        for (i, pos) in chunk.iter().map(|p| p.to_usize()).enumerate() {
+            if pos % step == 0 {
+                samples.push(pos);
+                words[i / 64] |= 1 << (i % 64);
+            }
+        }
+        (samples, words)
+    });
+    let count = parts.iter().map(|(s, _)| s.len()).sum();
+    let mut samples = IntVector::with_capacity(IntVector::width_for(sa.len() - 1), count);
+    let mut words = Vec::with_capacity(sa.len().div_ceil(64));
+    for (s, w) in parts {
+        for pos in s {
+            samples.push(pos);
+        }
+        words.extend_from_slice(&w);
+    }
+    (samples, BitVector::from_words(&words, sa.len()))
+}
+
+/// Sample every `step`-th SA row (rank sampling: no marker bits).
+fn sample_suffix_array_by_rank<I: SaIndex>(sa: &[I], step: usize) -> IntVector {
+    let mut samples =
+        IntVector::with_capacity(IntVector::width_for(sa.len() - 1), sa.len().div_ceil(step));
+    for pos in sa.iter().step_by(step) {
+        samples.push(pos.to_usize());
+    }
+    samples
+}
+
+/// Sample `ISA[pos]` for every text position that is a multiple of `step`
+/// (none when `step` is 0). `sa` includes the sentinel row.
+fn sample_inverse_suffix_array<I: SaIndex>(sa: &[I], step: usize) -> IntVector {
+    let text_len = sa.len() - 1;
+    let width = IntVector::width_for(text_len);
+    if step == 0 {
+        return IntVector::new(width);
+    }
+    let mut isa = IntVector::zeroed(width, text_len.div_ceil(step));
+    for (row, pos) in sa.iter().map(|p| p.to_usize()).enumerate() {
+        if pos < text_len && pos % step == 0 {
+            isa.set(pos / step, row);
+        }
+    }
+    isa
+}
+
+/// Size model behind [`AliceIndex::size_bytes`]; also predicts the size of
+/// an index before it is built (sample-step tuning)
+///
+/// `n` = text length + 1, `extra` = ISA samples and offset map bytes.
+/// Samples are packed at ⌈log₂ n⌉ bits.
+pub(crate) const fn modeled_size(
+    n: usize,
+    layers: usize,
+    sampling: SamplingStrategy,
+    sa_samples: usize,
+    extra: usize,
+) -> usize {
+    // WM: bits layers × (N/8 bytes for data + N/64 × 8 bytes for blocks)
+    // With interleaved layout: 9 u64 per 512 bits = 72 bytes per 512 bits
+    // = 1.125 bytes per bit × bits layers (≤ 9 bytes per character)
+    // plus the per-symbol run starts of the bottom ordering
+    let wm_size = n * 9 / 8 * layers + 256 * core::mem::size_of::<usize>(); // Approximate
+
+    // Alphabet: code + inverse tables
+    let alphabet_size = 2 * 256;
+
+    // C-Table: 256 × sizeof(usize) = 2KB on 64-bit
+    let c_table_size = 256 * core::mem::size_of::<usize>();
+
+    // SA sampled bits: interleaved layout (rank sampling needs no markers)
+    let sa_bits_size = match sampling {
+        SamplingStrategy::TextPosition => (n / 512 + 1) * 72, // 72 bytes per block
+        SamplingStrategy::SaRank => 0,
+    };
+
+    // SA samples: (N/step) × ⌈log₂ N⌉ bits, packed
+    let sa_samples_size = IntVector::bytes_for(sa_samples, IntVector::width_for(n - 1));
+
+    wm_size + alphabet_size + c_table_size + sa_bits_size + sa_samples_size + extra
+}
+
+/// Wavelet Matrix layers of an index over `text`: bit length of the largest
+/// code in the BWT (every text byte, plus the sentinel)
+pub(crate) fn modeled_layers(text: &[u8], compact_alphabet: bool) -> usize {
+    let max_code = if compact_alphabet {
+        Alphabet::from_text(text).sigma()
+    } else {
+        text.iter().copied().max().unwrap_or(0) as usize
+    };
+    (usize::BITS - max_code.leading_zeros()).max(1) as usize
+}
+
 /// ALICE-Search Index (FM-Index implementation)
 ///
 /// Searching implies counting.
 /// Count(Pattern) -> `O(Pattern_Length)` independent of Corpus Size.
 pub struct AliceIndex {
+    /// Byte → dense code mapping (alphabet compaction)
+    alphabet: Alphabet,
     /// Wavelet Matrix (stores BWT + Rank support)
     wm: WaveletMatrix,
     /// C-Table: Cumulative counts
     c_table: [usize; 256],
-    /// Suffix Array sampling step
-    sample_step: usize,
-    /// Sampled SA values (compact)
-    sa_samples: Vec<usize>,
+    /// Configuration the index was built with (sampling, folding, ...)
+    config: BuildConfig,
+    /// Sampled SA values (bit-packed)
+    sa_samples: IntVector,
     /// `BitVector` marking sampled positions (Fast locate!)
     sa_sampled_bits: BitVector,
+    /// `isa_samples[j]` = SA row of text position `j * isa_sample_step`
+    isa_samples: IntVector,
+    /// Folded → original text offsets (identity without folding)
+    offsets: OffsetMap,
+}
+
+/// Equal data and layout; `threads` and `memory_budget` are ignored
+impl PartialEq for AliceIndex {
+    fn eq(&self, other: &Self) -> bool {
+        self.config.same_layout(&other.config)
+            && self.alphabet == other.alphabet
+            && self.wm == other.wm
+            && self.c_table == other.c_table
+            && self.sa_samples == other.sa_samples
+            && self.sa_sampled_bits == other.sa_sampled_bits
+            && self.isa_samples == other.isa_samples
+            && self.offsets == other.offsets
+    }
 }
 
+impl Eq for AliceIndex {}
+
 impl AliceIndex {
     /// Build index from text
     ///
@@ -44,50 +316,282 @@ impl AliceIndex {
     /// - `text`: Input text to index
     /// - `sample_step`: SA sampling interval (trade-off: lower = faster locate, more memory)
     ///
+    /// The alphabet actually used by `text` is remapped to dense codes, so the
+    /// Wavelet Matrix has `⌈log₂(σ + 1)⌉` layers (3 for DNA, 4 for hex).
+    ///
     /// # Complexity
-    /// - Time: O(N log^2 N) with naive SA (use SA-IS for O(N) in production)
-    /// - Space: O(N * 1.125) for WM + `O(N/sample_step)` for SA samples
+    /// - Time: O(N) (SA-IS)
+    /// - Space: O(N * 1.125 * log σ / 8) for WM + `O(N/sample_step)` for SA samples
     #[must_use]
     pub fn build(text: &[u8], sample_step: usize) -> Self {
-        let sample_step = sample_step.max(1);
+        Self::build_with_options(text, &IndexOptions::with_sample_step(sample_step))
+    }
 
-        // 1. Build SA & BWT
-        let sa = build_suffix_array(text);
+    /// Build index from text with explicit options
+    ///
+    /// # Example
+    /// ```
+    /// use alice_search::{AliceIndex, IndexOptions};
+    ///
+    /// let options = IndexOptions { sample_step: 8, compact_alphabet: false, threads: 2 };
+    /// let index = AliceIndex::build_with_options(b"abracadabra", &options);
+    /// assert_eq!(index.count(b"bra"), 2);
+    /// ```
+    #[must_use]
+    pub fn build_with_options(text: &[u8], options: &IndexOptions) -> Self {
+        Self::build_with_stats(text, options).0
+    }
 
-        // Reconstruct BWT string for WM construction
-        let mut bwt = Vec::with_capacity(sa.len());
-        for &idx in &sa {
-            if idx == 0 {
-                bwt.push(SENTINEL);
-            } else {
-                bwt.push(text[idx - 1]);
+    /// Build and report the peak memory of the build buffers.
+    ///
+    /// Texts shorter than `u32::MAX` bytes use a `u32` suffix array (4 bytes
+    /// per entry instead of 8). The text is never copied for SA-IS, and the
+    /// suffix array is released as soon as the BWT and the samples are
+    /// extracted, before the Wavelet Matrix buffers are allocated.
+    ///
+    /// # Example
+    /// ```
+    /// use alice_search::{AliceIndex, IndexOptions};
+    ///
+    /// let (index, stats) = AliceIndex::build_with_stats(b"abracadabra", &IndexOptions::default());
+    /// assert_eq!(index.count(b"abra"), 2);
+    /// assert_eq!(stats.sa_entry_bytes, 4);
+    /// assert!(stats.peak_bytes >= 12 * 4);
+    /// ```
+    #[must_use]
+    pub fn build_with_stats(text: &[u8], options: &IndexOptions) -> (Self, BuildStats) {
+        build::uncancelled(Self::build_as(text, options, &mut Progress::none()))
+    }
+
+    /// Build while reporting progress to `observer`, which may cancel
+    ///
+    /// Phases arrive in order: every SA-IS level, BWT, sampling, then each
+    /// Wavelet Matrix layer, each with a fraction from 0.0 to 1.0.
+    ///
+    /// # Errors
+    /// [`BuildError::Cancelled`] if the observer returned `ControlFlow::Break`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use alice_search::{AliceIndex, BuildPhase, IndexOptions};
+    ///
+    /// let mut phases = Vec::new();
+    /// let index = AliceIndex::build_observed(b"abracadabra", &IndexOptions::default(), &mut |phase, _| {
+    ///     phases.push(phase);
+    ///     ControlFlow::Continue(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(index.count(b"abra"), 2);
+    /// assert!(phases.contains(&BuildPhase::Bwt));
+    /// ```
+    pub fn build_observed(
+        text: &[u8],
+        options: &IndexOptions,
+        observer: &mut dyn BuildObserver,
+    ) -> Result<Self, BuildError> {
+        Self::build_as(text, options, &mut Progress::new(observer)).map(|(index, _)| index)
+    }
+
+    fn build_as(
+        text: &[u8],
+        options: &IndexOptions,
+        progress: &mut Progress<'_>,
+    ) -> Result<(Self, BuildStats), BuildError> {
+        Self::build_configured(text, &BuildConfig::from(options), progress)
+    }
+
+    /// Fold the text if configured, resolve the sample step, check the
+    /// memory budget, then build
+    pub(crate) fn build_configured(
+        text: &[u8],
+        config: &BuildConfig,
+        progress: &mut Progress<'_>,
+    ) -> Result<(Self, BuildStats), BuildError> {
+        let (text, offsets) = config.transform(text);
+        let plan = config.plan(&text, &offsets)?;
+        let config = &BuildConfig {
+            sample_step: plan.sample_step,
+            ..*config
+        };
+        if let Some(budget) = config.memory_budget {
+            let required = config.estimated_peak_bytes(text.len());
+            if required > budget {
+                return Err(BuildError::MemoryBudgetExceeded { required, budget });
             }
         }
+        let sa_entry_bytes = if u32::fits(text.len()) {
+            4
+        } else {
+            core::mem::size_of::<usize>()
+        };
+        let (mut index, peak_bytes) = if sa_entry_bytes == 4 {
+            Self::build_typed::<u32>(&text, config, progress)?
+        } else {
+            Self::build_typed::<usize>(&text, config, progress)?
+        };
+        index.offsets = offsets;
+        let stats = BuildStats {
+            peak_bytes,
+            sa_entry_bytes,
+            index_bytes: index.size_bytes(),
+            plan,
+        };
+        Ok((index, stats))
+    }
+
+    /// Build from a reader (pipe, decompressor, file), read to EOF
+    ///
+    /// # Errors
+    /// Returns the first I/O error of the reader.
+    ///
+    /// # Example
+    /// ```
+    /// use alice_search::{AliceIndex, IndexOptions};
+    ///
+    /// let input: &[u8] = b"abracadabra";
+    /// let index = AliceIndex::build_from_reader(input, &IndexOptions::default()).unwrap();
+    /// assert_eq!(index.count(b"abra"), 2);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn build_from_reader(
+        reader: impl std::io::Read,
+        options: &IndexOptions,
+    ) -> std::io::Result<Self> {
+        Self::build_from_reader_with_hint(reader, 0, options)
+    }
 
-        // 2. Build Wavelet Matrix (Double-buffered, zero intermediate allocs)
-        let wm = WaveletMatrix::build(&bwt);
-        let c_table = build_c_table(&bwt);
+    /// Build from a reader, reserving `size_hint` bytes up front
+    ///
+    /// An exact hint avoids the regrowth copies of the read buffer. The hint
+    /// is only a reservation request: one that cannot be reserved (e.g.
+    /// `usize::MAX`) is ignored, too small a hint costs reallocations and
+    /// too large a hint leaves unused capacity for the duration of the build.
+    ///
+    /// # Errors
+    /// Returns the first I/O error of the reader.
+    #[cfg(feature = "std")]
+    pub fn build_from_reader_with_hint(
+        reader: impl std::io::Read,
+        size_hint: usize,
+        options: &IndexOptions,
+    ) -> std::io::Result<Self> {
+        Self::build_from_readers(core::iter::once(reader), size_hint, options)
+    }
+
+    /// Build over the concatenation of several readers, read one by one
+    ///
+    /// Documents can be streamed in without the caller assembling one
+    /// buffer; `size_hint` is the expected total length (0 if unknown).
+    ///
+    /// # Errors
+    /// Returns the first I/O error of any reader.
+    #[cfg(feature = "std")]
+    pub fn build_from_readers<R: std::io::Read>(
+        readers: impl IntoIterator<Item = R>,
+        size_hint: usize,
+        options: &IndexOptions,
+    ) -> std::io::Result<Self> {
+        let mut text = Vec::new();
+        // Best effort: a bogus hint must not abort the build
+        let _ = text.try_reserve(size_hint);
+        for mut reader in readers {
+            reader.read_to_end(&mut text)?;
+        }
+        Ok(Self::build_with_options(&text, options))
+    }
 
-        // 3. Build SA Samples with BitVector
-        let mut sa_samples = Vec::new();
-        let mut sa_sampled_bits = BitVector::new();
+    fn build_typed<I: SaIndex>(
+        text: &[u8],
+        config: &BuildConfig,
+        progress: &mut Progress<'_>,
+    ) -> Result<(Self, usize), BuildError> {
+        let sample_step = config.sample_step.max(1);
+        let threads = parallel::resolve_threads(config.threads);
+        let mut meter = PeakMeter::with_budget(config.memory_budget);
 
-        for &pos in &sa {
-            if pos % sample_step == 0 {
-                sa_samples.push(pos);
-                sa_sampled_bits.push(true);
-            } else {
-                sa_sampled_bits.push(false);
+        // 1. Build SA & BWT
+        let sa: Vec<I> = build_suffix_array_metered(text, threads, &mut meter, progress)?;
+        let n = sa.len();
+        let alphabet = if config.compact_alphabet {
+            Alphabet::from_text(text)
+        } else {
+            Alphabet::identity()
+        };
+
+        // Reconstruct BWT string (in dense codes) for WM construction
+        progress.report(BuildPhase::Bwt, 0.0)?;
+        meter.alloc(n)?;
+        let ranges = parallel::chunk_ranges(n, threads, 1);
+        let mut bwt = vec![SENTINEL; n];
+        let dst = parallel::split_lengths(&mut bwt, ranges.iter().map(|r| r.len()));
+        parallel::scope_map(ranges.into_iter().zip(dst).collect(), |(r, dst)| {
+            for (b, &idx) in dst.iter_mut().zip(&sa[r]) {
+                if let Some(prev) = idx.to_usize().checked_sub(1) {
+                    *b = alphabet.code(text[prev]);
+                }
             }
-        }
-        sa_sampled_bits.build_index();
+        });
+
+        progress.report(BuildPhase::Bwt, 1.0)?;
+
+        // 2. Build SA Samples with BitVector, then release the SA
+        progress.report(BuildPhase::Sampling, 0.0)?;
+        let (sa_samples, sa_sampled_bits) = match config.sampling {
+            SamplingStrategy::TextPosition => sample_suffix_array(&sa, sample_step, threads),
+            SamplingStrategy::SaRank => (
+                sample_suffix_array_by_rank(&sa, sample_step),
+                BitVector::new(),
+            ),
+        };
+        meter.alloc(sa_samples.size_bytes() + sa_sampled_bits.len().div_ceil(512) * 72)?;
+        let isa_samples = sample_inverse_suffix_array(&sa, config.isa_sample_step);
+        meter.alloc(isa_samples.size_bytes())?;
+        drop(sa);
+        meter.free(n * core::mem::size_of::<I>());
+        progress.report(BuildPhase::Sampling, 1.0)?;
+
+        // 3. Build Wavelet Matrix (Double-buffered, zero intermediate allocs)
+        let wm_bytes = WaveletMatrix::layer_count(&bwt) * n.div_ceil(512) * 72;
+        meter.alloc(2 * n + wm_bytes)?;
+        let wm = WaveletMatrix::build_observed(&bwt, threads, progress)?;
+        meter.free(2 * n);
+        let c_table = build_c_table(&bwt);
 
+        let index = Self {
+            alphabet,
+            wm,
+            c_table,
+            config: *config,
+            sa_samples,
+            sa_sampled_bits,
+            isa_samples,
+            offsets: OffsetMap::identity(text.len()),
+        };
+        Ok((index, meter.peak()))
+    }
+
+    /// Assemble an index from components built elsewhere (external build).
+    pub(crate) fn from_parts(
+        alphabet: Alphabet,
+        wm: WaveletMatrix,
+        c_table: [usize; 256],
+        config: BuildConfig,
+        sa_samples: &[usize],
+        sa_sampled_bits: BitVector,
+    ) -> Self {
+        let text_len = wm.len().saturating_sub(1);
+        let offsets = OffsetMap::identity(text_len);
+        let sa_samples = IntVector::from_slice(sa_samples, text_len);
         Self {
+            alphabet,
             wm,
             c_table,
-            sample_step,
+            config,
             sa_samples,
             sa_sampled_bits,
+            isa_samples: IntVector::new(IntVector::width_for(text_len)),
+            offsets,
         }
     }
 
@@ -105,10 +609,33 @@ impl AliceIndex {
     #[inline(always)]
     #[must_use]
     pub fn count(&self, pattern: &[u8]) -> usize {
-        let range = self.backward_search(pattern);
+        let range = self.search_range(pattern);
         range.end - range.start
     }
 
+    /// Count occurrences of many patterns: `count` for each pattern
+    ///
+    /// The backward searches of up to `BATCH_LANES` patterns advance
+    /// together, one character per round, and their Wavelet Matrix accesses
+    /// are interleaved with software prefetch (`rank_pair_batch`), so the
+    /// cache misses of different patterns overlap. Pays off from a few dozen
+    /// patterns on, e.g. dictionary lookups; see the `count_batch` bench.
+    ///
+    /// # Example
+    /// ```
+    /// use alice_search::AliceIndex;
+    ///
+    /// let index = AliceIndex::build(b"abracadabra", 4);
+    /// assert_eq!(index.count_batch(&[b"abra", b"a", b"xyz"]), [2, 5, 0]);
+    /// ```
+    #[must_use]
+    pub fn count_batch(&self, patterns: &[&[u8]]) -> Vec<usize> {
+        self.search_range_batch(patterns)
+            .into_iter()
+            .map(|range| range.len())
+            .collect()
+    }
+
     /// Locate all positions where pattern occurs (Iterator version)
     ///
     /// **Zero Allocation**: Returns a lazy iterator instead of Vec.
@@ -128,10 +655,23 @@ impl AliceIndex {
     #[inline(always)]
     #[must_use]
     pub fn locate<'a>(&'a self, pattern: &'a [u8]) -> LocateIter<'a> {
-        let range = self.backward_search(pattern);
+        let range = self.search_range(pattern);
         LocateIter { index: self, range }
     }
 
+    /// Locate the suffixes of an SA range obtained from `search_range()`
+    ///
+    /// Lets callers run backward search once and enumerate positions later.
+    #[inline]
+    #[must_use]
+    pub fn locate_range(&self, range: Range<usize>) -> LocateIter<'_> {
+        let end = range.end.min(self.wm.len());
+        LocateIter {
+            index: self,
+            range: range.start.min(end)..end,
+        }
+    }
+
     /// Locate all positions (collecting into Vec for convenience)
     ///
     /// Use `locate()` iterator for zero-allocation queries.
@@ -140,70 +680,249 @@ impl AliceIndex {
         self.locate(pattern).collect()
     }
 
+    /// Locate many patterns: `locate_all` for each pattern, in the same order
+    ///
+    /// Backward searches are batched as in `count_batch`; the LF walks that
+    /// resolve the occurrences of all patterns then share lanes the same
+    /// way (`get_and_rank_batch`).
+    #[must_use]
+    pub fn locate_batch(&self, patterns: &[&[u8]]) -> Vec<Vec<usize>> {
+        let ranges = self.search_range_batch(patterns);
+        let mut positions: Vec<Vec<usize>> = ranges.iter().map(|r| vec![0; r.len()]).collect();
+
+        // (pattern, output slot, SA row, LF steps taken) per occurrence
+        let mut rows = ranges.into_iter().enumerate().flat_map(|(k, range)| {
+            range
+                .enumerate()
+                .map(move |(slot, row)| (k, slot, row, 0usize))
+        });
+        let mut lanes = Vec::with_capacity(BATCH_LANES);
+        let mut cursors = Vec::with_capacity(BATCH_LANES);
+        let mut out = Vec::with_capacity(BATCH_LANES);
+
+        loop {
+            lanes.extend(rows.by_ref().take(BATCH_LANES - lanes.len()));
+            if lanes.is_empty() {
+                return positions;
+            }
+
+            // Retire rows that reached a sample
+            let mut j = 0;
+            while j < lanes.len() {
+                let (k, slot, row, steps) = lanes[j];
+                if let Some(pos) = self.sampled_position(row) {
+                    positions[k][slot] = self.offsets.to_original(pos + steps);
+                    lanes.swap_remove(j);
+                } else {
+                    j += 1;
+                }
+            }
+
+            // One LF step for the rest
+            cursors.clear();
+            cursors.extend(lanes.iter().map(|lane| lane.2));
+            out.resize(cursors.len(), (0, 0));
+            self.wm.get_and_rank_batch(&cursors, &mut out);
+            for j in (0..lanes.len()).rev() {
+                let (c, rank) = out[j];
+                let (k, slot, row, steps) = &mut lanes[j];
+                if c == SENTINEL {
+                    positions[*k][*slot] = self.offsets.to_original(*steps); // Start of text
+                    lanes.swap_remove(j);
+                } else {
+                    *row = self.c_table[c as usize] + rank;
+                    *steps += 1;
+                }
+            }
+        }
+    }
+
     /// Check if pattern exists in text
     #[inline(always)]
     #[must_use]
     pub fn contains(&self, pattern: &[u8]) -> bool {
-        !self.backward_search(pattern).is_empty()
+        !self.search_range(pattern).is_empty()
     }
 
     /// Get the range in suffix array for a pattern
     ///
+    /// Applies the configured case folding to the pattern; patterns that
+    /// contain the configured separator match nothing.
     /// Useful for advanced operations
     #[inline(always)]
     #[must_use]
     pub fn search_range(&self, pattern: &[u8]) -> Range<usize> {
-        self.backward_search(pattern)
+        if let Some(separator) = self.config.separator {
+            if pattern.contains(&separator) {
+                return 0..0;
+            }
+        }
+        #[cfg(feature = "normalize")]
+        if self.config.normalization.is_some() {
+            return self.backward_search(&self.config.transform(pattern).0);
+        }
+        match self.config.folding {
+            None => self.backward_search(pattern),
+            Some(CaseFolding::Ascii) => {
+                self.backward_search_rev(pattern.iter().rev().map(|&b| ascii_lower(b)))
+            }
+            Some(folding) => self.backward_search_rev(FoldRev::new(pattern, folding)),
+        }
     }
 
-    /// Resolve SA[i] using LF-mapping walk + `BitVector` check
-    /// `O(sample_step)` - No linear scan!
+    /// `search_range` for many patterns, backward searches interleaved
+    ///
+    /// See `count_batch`.
+    #[must_use]
+    pub fn search_range_batch(&self, patterns: &[&[u8]]) -> Vec<Range<usize>> {
+        let mut ranges = vec![0..0; patterns.len()];
+
+        // Alphabet codes of every (folded) pattern, last byte first
+        let mut codes = Vec::new();
+        let mut pending = Vec::with_capacity(patterns.len());
+        for (k, pattern) in patterns.iter().enumerate() {
+            if let Some(separator) = self.config.separator {
+                if pattern.contains(&separator) {
+                    continue;
+                }
+            }
+            let start = codes.len();
+            #[cfg(feature = "normalize")]
+            let normalized = self
+                .config
+                .normalization
+                .map(|_| self.config.transform(pattern).0);
+            #[cfg(not(feature = "normalize"))]
+            let normalized: Option<&[u8]> = None;
+            match self.config.folding {
+                _ if normalized.is_some() => {
+                    codes.extend(normalized.iter().flat_map(|n| n.iter().rev()));
+                }
+                None => codes.extend(pattern.iter().rev()),
+                Some(CaseFolding::Ascii) => {
+                    codes.extend(pattern.iter().rev().map(|&b| ascii_lower(b)));
+                }
+                Some(folding) => codes.extend(FoldRev::new(pattern, folding)),
+            }
+            for b in &mut codes[start..] {
+                *b = self.alphabet.code(*b);
+            }
+            if codes[start..].contains(&SENTINEL) {
+                codes.truncate(start); // Byte outside the alphabet
+                continue;
+            }
+            ranges[k] = 0..self.wm.len();
+            if start < codes.len() {
+                pending.push((k, start..codes.len()));
+            }
+        }
+
+        // Lanes: (pattern, codes still to consume)
+        let mut pending = pending.into_iter();
+        let mut lanes: Vec<_> = pending.by_ref().take(BATCH_LANES).collect();
+        let mut queries = Vec::with_capacity(BATCH_LANES);
+        let mut out = Vec::with_capacity(BATCH_LANES);
+
+        while !lanes.is_empty() {
+            queries.clear();
+            queries.extend(lanes.iter().map(|(k, rest): &(usize, Range<usize>)| {
+                (codes[rest.start], ranges[*k].start, ranges[*k].end)
+            }));
+            out.resize(queries.len(), (0, 0));
+            self.wm.rank_pair_batch(&queries, &mut out);
+
+            // Backwards, so `swap_remove` only moves finished-this-round lanes
+            for j in (0..lanes.len()).rev() {
+                let (k, rest) = &mut lanes[j];
+                let base = self.c_table[codes[rest.start] as usize];
+                let (sp, ep) = (base + out[j].0, base + out[j].1);
+                rest.start += 1;
+                ranges[*k] = if sp < ep { sp..ep } else { 0..0 };
+                if sp >= ep || rest.start == rest.end {
+                    match pending.next() {
+                        Some(next) => lanes[j] = next,
+                        None => {
+                            lanes.swap_remove(j);
+                        }
+                    }
+                }
+            }
+        }
+        ranges
+    }
+
+    /// Resolve SA[i] using LF-mapping walk until a sampled row
+    /// `O(sample_step)` with text-position sampling - No linear scan!
     fn resolve_sa(&self, mut i: usize) -> usize {
         let mut steps = 0;
 
         loop {
-            // 1. Check if sampled (O(1) with interleaved BitVector)
-            if self.sa_sampled_bits.get(i) {
-                // Find index in samples vector using rank1 (O(1))
-                let idx = self.sa_sampled_bits.rank1(i);
-                return self.sa_samples[idx] + steps;
+            // 1. Check if sampled
+            if let Some(pos) = self.sampled_position(i) {
+                return pos + steps;
             }
 
-            // 2. Walk backwards (LF-mapping)
-            let c = self.wm.get(i);
+            // 2. Walk backwards (LF-mapping, one fused wavelet pass)
+            let (c, rank) = self.wm.get_and_rank(i);
             if c == SENTINEL {
                 return steps; // Hit the start of text
             }
 
-            let rank = self.wm.rank(c, i);
             i = self.c_table[c as usize] + rank;
             steps += 1;
         }
     }
 
+    /// SA value of row `i` if the row is sampled
+    #[inline(always)]
+    fn sampled_position(&self, i: usize) -> Option<usize> {
+        match self.config.sampling {
+            // Marker bit (O(1) with interleaved BitVector), then rank1 into the samples
+            SamplingStrategy::TextPosition => self
+                .sa_sampled_bits
+                .get(i)
+                .then(|| self.sa_samples.get(self.sa_sampled_bits.rank1(i))),
+            // Every `sample_step`-th row: no marker lookup
+            SamplingStrategy::SaRank => {
+                let step = self.config.sample_step;
+                let (row, offset) = (i / step, i % step);
+                (offset == 0).then(|| self.sa_samples.get(row))
+            }
+        }
+    }
+
     /// Backward Search Algorithm (FM-Index Core)
     ///
     /// Returns the range [sp, ep) in the suffix array where
     /// all suffixes starting with `pattern` are located.
     #[inline(always)]
     fn backward_search(&self, pattern: &[u8]) -> Range<usize> {
-        if pattern.is_empty() {
-            return 0..self.wm.len();
-        }
+        self.backward_search_rev(pattern.iter().rev().copied())
+    }
 
+    /// Backward search over pattern bytes supplied last-to-first
+    ///
+    /// Lets folded indexes transform the pattern on the fly without
+    /// materializing it. An empty sequence matches every suffix.
+    #[inline(always)]
+    pub(crate) fn backward_search_rev<I>(&self, rev_pattern: I) -> Range<usize>
+    where
+        I: Iterator<Item = u8>,
+    {
         let mut sp = 0;
         let mut ep = self.wm.len();
 
         // Process pattern from last char to first (backward)
-        for &c in pattern.iter().rev() {
+        for b in rev_pattern {
+            // Bytes outside the alphabet map to the sentinel code
+            let c = self.alphabet.code(b);
             if c == SENTINEL {
                 return 0..0;
             }
             let c_idx = c as usize;
 
-            // WM Rank is O(8) [fixed 8 steps for u8]
-            let rank_sp = self.wm.rank(c, sp);
-            let rank_ep = self.wm.rank(c, ep);
+            // WM Rank is O(bits) [at most 8 steps for u8], both ends in one pass
+            let (rank_sp, rank_ep) = self.wm.rank_pair(c, sp, ep);
 
             sp = self.c_table[c_idx] + rank_sp;
             ep = self.c_table[c_idx] + rank_ep;
@@ -215,33 +934,83 @@ impl AliceIndex {
         sp..ep
     }
 
-    /// Index size in bytes (approximate)
+    /// Reconstruct the indexed text by inverse BWT (LF-walk from the sentinel)
+    ///
+    /// O(N log σ). Used to rebuild an index without keeping the original text.
     #[must_use]
-    pub const fn size_bytes(&self) -> usize {
-        let n = self.wm.len();
-
-        // WM: 8 layers × (N/8 bytes for data + N/64 × 8 bytes for blocks)
-        // With interleaved layout: 9 u64 per 512 bits = 72 bytes per 512 bits
-        // = 1.125 bytes per bit × 8 layers = 9 bytes per character
-        let wm_size = n * 9 / 8 * 8; // Approximate
-
-        // C-Table: 256 × sizeof(usize) = 2KB on 64-bit
-        let c_table_size = 256 * core::mem::size_of::<usize>();
-
-        // SA sampled bits: interleaved layout
-        let sa_bits_size = (n / 512 + 1) * 72; // 72 bytes per block
+    pub fn extract_text(&self) -> Vec<u8> {
+        self.extract(0..self.text_len())
+    }
 
-        // SA samples: (N/step) × sizeof(usize)
-        let sa_samples_size = self.sa_samples.len() * core::mem::size_of::<usize>();
+    /// Extract `range` of the indexed (folded) text
+    ///
+    /// Walks LF backwards from the first ISA sample at or after `range.end`:
+    /// O((len + `isa_sample_step`) log σ), or O((N - start) log σ) when the
+    /// index was built without ISA samples.
+    ///
+    /// # Panics
+    /// If `range` is reversed or ends past `text_len()`.
+    #[must_use]
+    pub fn extract(&self, range: Range<usize>) -> Vec<u8> {
+        let n = self.text_len();
+        assert!(
+            range.start <= range.end && range.end <= n,
+            "extract range {range:?} out of bounds for length {n}"
+        );
+        // Row 0 is the sentinel suffix; its BWT symbol is the last text byte
+        let (mut pos, mut i) = (n, 0);
+        let step = self.config.isa_sample_step;
+        if step > 0 {
+            let j = range.end.div_ceil(step);
+            if j < self.isa_samples.len() {
+                (pos, i) = (j * step, self.isa_samples.get(j));
+            }
+        }
+        let mut text = vec![0u8; range.len()];
+        while pos > range.start {
+            let (c, rank) = self.wm.get_and_rank(i);
+            pos -= 1;
+            if pos < range.end {
+                text[pos - range.start] = self.alphabet.byte(c);
+            }
+            i = self.c_table[c as usize] + rank;
+        }
+        text
+    }
 
-        wm_size + c_table_size + sa_bits_size + sa_samples_size
+    /// Index size in bytes (approximate)
+    #[must_use]
+    pub const fn size_bytes(&self) -> usize {
+        // ISA samples and the folding offset map (both empty by default)
+        let extra = self.isa_samples.size_bytes() + self.offsets.size_bytes();
+        modeled_size(
+            self.wm.len(),
+            self.wm.bits(),
+            self.config.sampling,
+            self.sa_samples.len(),
+            extra,
+        )
     }
 
     /// Get the SA sampling step
     #[inline]
     #[must_use]
     pub const fn sample_step(&self) -> usize {
-        self.sample_step
+        self.config.sample_step
+    }
+
+    /// Configuration the index was built with
+    #[inline]
+    #[must_use]
+    pub const fn config(&self) -> &BuildConfig {
+        &self.config
+    }
+
+    /// Alphabet (byte → dense code mapping) detected at build time
+    #[inline]
+    #[must_use]
+    pub const fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
     }
 
     /// Original text length (excluding sentinel)
@@ -279,7 +1048,7 @@ impl Iterator for LocateIter<'_> {
         }
         let pos = self.index.resolve_sa(self.range.start);
         self.range.start += 1;
-        Some(pos)
+        Some(self.index.offsets.to_original(pos))
     }
 
     #[inline]
@@ -299,8 +1068,12 @@ impl ExactSizeIterator for LocateIter<'_> {}
 ///
 /// ビルド時にテキストを小文字正規化するため、
 /// 検索も自動的に case-insensitive になる。
+/// Unicode の折り畳みでは `OffsetMap` により元テキストの位置を返す。
 pub struct CaseInsensitiveIndex {
     inner: AliceIndex,
+    folding: CaseFolding,
+    /// 折り畳み後 → 元テキストの位置対応
+    offsets: OffsetMap,
 }
 
 impl CaseInsensitiveIndex {
@@ -309,133 +1082,133 @@ impl CaseInsensitiveIndex {
     /// テキストを ASCII 小文字に正規化してからインデックスを作成する。
     #[must_use]
     pub fn build(text: &[u8], sample_step: usize) -> Self {
-        let lower: Vec<u8> = text.iter().map(|&b| ascii_lower(b)).collect();
+        Self::build_with_folding(text, sample_step, CaseFolding::Ascii)
+    }
+
+    /// 折り畳み方式を指定してインデックスを構築する。
+    ///
+    /// `Simple` / `Full` はテキストを UTF-8 として扱う。
+    #[must_use]
+    pub fn build_with_folding(text: &[u8], sample_step: usize, folding: CaseFolding) -> Self {
+        Self::build_with_options(text, &IndexOptions::with_sample_step(sample_step), folding)
+    }
+
+    /// `AliceIndex` と同じビルドオプションで構築する。
+    #[must_use]
+    pub fn build_with_options(text: &[u8], options: &IndexOptions, folding: CaseFolding) -> Self {
+        let (folded, offsets) = folding.fold(text);
         Self {
-            inner: AliceIndex::build(&lower, sample_step),
+            inner: AliceIndex::build_with_options(&folded, options),
+            folding,
+            offsets,
         }
     }
 
-    /// パターンの出現回数（case-insensitive）。
+    /// パターンの出現回数（case-insensitive）。パターンは検索中に折り畳む。
+    #[inline]
     #[must_use]
     pub fn count(&self, pattern: &[u8]) -> usize {
-        let lower: Vec<u8> = pattern.iter().map(|&b| ascii_lower(b)).collect();
-        self.inner.count(&lower)
+        let range = self.search_range(pattern);
+        range.end - range.start
     }
 
     /// パターンが存在するか（case-insensitive）。
+    #[inline]
     #[must_use]
     pub fn contains(&self, pattern: &[u8]) -> bool {
-        let lower: Vec<u8> = pattern.iter().map(|&b| ascii_lower(b)).collect();
-        self.inner.contains(&lower)
+        !self.search_range(pattern).is_empty()
     }
 
-    /// 全出現位置を返す（case-insensitive）。
+    /// 出現位置（元テキストのバイトオフセット）を返すイテレータ。
+    ///
+    /// **Zero Allocation**: パターンの折り畳みも結果も割り当てを行わない。
+    #[inline]
     #[must_use]
-    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
-        let lower: Vec<u8> = pattern.iter().map(|&b| ascii_lower(b)).collect();
-        self.inner.locate_all(&lower)
+    pub fn locate(&self, pattern: &[u8]) -> FoldedLocateIter<'_> {
+        self.locate_range(self.search_range(pattern))
     }
 
-    /// テキスト長。
+    /// `search_range()` で得た SA 範囲の出現位置を返すイテレータ。
+    #[inline]
     #[must_use]
-    pub const fn text_len(&self) -> usize {
-        self.inner.text_len()
+    pub fn locate_range(&self, range: Range<usize>) -> FoldedLocateIter<'_> {
+        FoldedLocateIter::new(self.inner.locate_range(range), &self.offsets)
     }
-}
 
-/// ASCII 大文字を小文字に変換する。非 ASCII はそのまま。
-#[inline]
-const fn ascii_lower(b: u8) -> u8 {
-    if b >= b'A' && b <= b'Z' {
-        b + 32
-    } else {
-        b
+    /// 全出現位置を返す（case-insensitive）。位置は元テキストのバイトオフセット。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        self.locate(pattern).collect()
     }
-}
-
-// ============================================================================
-// Incremental index (append + rebuild)
-// ============================================================================
 
-/// インクリメンタルインデックスビルダー。
-///
-/// テキストを逐次追加し、`rebuild()` で検索可能なインデックスを再構築する。
-/// 差分ビルドではなくフル再構築だが、追加 API を提供することで
-/// ユーザーコードがテキスト管理を自前で行う必要をなくす。
-pub struct IncrementalIndex {
-    /// 蓄積されたテキスト。
-    buffer: Vec<u8>,
-    /// SA サンプリングステップ。
-    sample_step: usize,
-    /// 現在のインデックス（`rebuild()` 後に有効）。
-    index: Option<AliceIndex>,
-}
-
-impl IncrementalIndex {
-    /// 新規作成。
+    /// 折り畳み後テキストの接尾辞配列上でのパターンの範囲。
+    #[inline]
     #[must_use]
-    pub fn new(sample_step: usize) -> Self {
-        Self {
-            buffer: Vec::new(),
-            sample_step: sample_step.max(1),
-            index: None,
+    pub fn search_range(&self, pattern: &[u8]) -> Range<usize> {
+        if self.folding == CaseFolding::Ascii {
+            self.inner
+                .backward_search_rev(pattern.iter().rev().map(|&b| ascii_lower(b)))
+        } else {
+            self.inner
+                .backward_search_rev(FoldRev::new(pattern, self.folding))
         }
     }
 
-    /// テキストを追加する。
-    ///
-    /// 追加後は `rebuild()` を呼ぶまで検索結果に反映されない。
-    pub fn append(&mut self, text: &[u8]) {
-        self.buffer.extend_from_slice(text);
-    }
-
-    /// 蓄積されたテキストからインデックスを再構築する。
-    pub fn rebuild(&mut self) {
-        self.index = Some(AliceIndex::build(&self.buffer, self.sample_step));
+    /// 元テキストの長さ。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.offsets.original_len()
     }
 
-    /// パターンの出現回数。`rebuild()` 後に有効。
+    /// 折り畳み方式。
     #[must_use]
-    pub fn count(&self, pattern: &[u8]) -> usize {
-        self.index.as_ref().map_or(0, |idx| idx.count(pattern))
+    pub const fn folding(&self) -> CaseFolding {
+        self.folding
     }
 
-    /// パターンが存在するか。
+    /// SA サンプリングステップ。
     #[must_use]
-    pub fn contains(&self, pattern: &[u8]) -> bool {
-        self.index.as_ref().is_some_and(|idx| idx.contains(pattern))
+    pub const fn sample_step(&self) -> usize {
+        self.inner.sample_step()
     }
 
-    /// 全出現位置。
+    /// インデックスの概算バイトサイズ（位置対応表を含む）。
     #[must_use]
-    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
-        self.index
-            .as_ref()
-            .map_or_else(Vec::new, |idx| idx.locate_all(pattern))
+    pub fn size_bytes(&self) -> usize {
+        self.inner.size_bytes() + self.offsets.size_bytes()
     }
 
-    /// 蓄積テキストの長さ。
+    /// 圧縮率: `size_bytes` / 元テキスト長。
     #[must_use]
-    pub const fn buffer_len(&self) -> usize {
-        self.buffer.len()
+    pub fn compression_ratio(&self) -> f64 {
+        let text_len = self.text_len();
+        if text_len == 0 {
+            return 0.0;
+        }
+        self.size_bytes() as f64 / text_len as f64
     }
 
-    /// インデックスが構築済みか。
+    /// 内部の `AliceIndex`（折り畳み後テキストのインデックス）。
     #[must_use]
-    pub const fn is_built(&self) -> bool {
-        self.index.is_some()
+    pub const fn inner(&self) -> &AliceIndex {
+        &self.inner
     }
+}
 
-    /// 蓄積テキストをクリアしてインデックスをリセットする。
-    pub fn clear(&mut self) {
-        self.buffer.clear();
-        self.index = None;
+/// ASCII 大文字を小文字に変換する。非 ASCII はそのまま。
+#[inline]
+const fn ascii_lower(b: u8) -> u8 {
+    if b >= b'A' && b <= b'Z' {
+        b + 32
+    } else {
+        b
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::ops::ControlFlow;
 
     #[test]
     fn test_backward_search() {
@@ -739,6 +1512,69 @@ mod tests {
         assert_eq!(results[0], vec![1, 4]);
     }
 
+    #[test]
+    fn test_extract_text_roundtrip() {
+        for text in [&b"abracadabra"[..], b"", b"a", b"mississippi\x01\xff"] {
+            let index = AliceIndex::build(text, 3);
+            assert_eq!(index.extract_text(), text);
+        }
+        let options = IndexOptions {
+            compact_alphabet: false,
+            ..IndexOptions::with_sample_step(2)
+        };
+        let index = AliceIndex::build_with_options(b"banana bandana", &options);
+        assert_eq!(index.extract_text(), b"banana bandana");
+    }
+
+    // ====================================================================
+    // アルファベット圧縮テスト
+    // ====================================================================
+
+    #[test]
+    fn test_alphabet_compaction_dna() {
+        // ACGT + センチネル = 5 記号 → 3 レイヤー
+        let text = b"ACGTACGTTTGACCAGT";
+        let index = AliceIndex::build(text, 2);
+
+        assert_eq!(index.alphabet().sigma(), 4);
+        assert_eq!(index.wm.bits(), 3);
+        assert_eq!(index.count(b"ACGT"), 2);
+        assert_eq!(index.count(b"T"), 5);
+        let mut positions = index.locate_all(b"CAG");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![13]);
+    }
+
+    #[test]
+    fn test_alphabet_byte_outside_short_circuits() {
+        let index = AliceIndex::build(b"0123456789", 4);
+        assert_eq!(index.alphabet().code(b'a'), SENTINEL);
+        assert_eq!(index.count(b"a"), 0);
+        assert_eq!(index.count(b"12a"), 0);
+        assert!(index.search_range(b"9a").is_empty());
+        assert_eq!(index.count(b"345"), 1);
+    }
+
+    #[test]
+    fn test_alphabet_codes_preserve_order() {
+        let alphabet = Alphabet::from_text(b"zebra");
+        assert_eq!(alphabet.sigma(), 5);
+        assert!(alphabet.code(b'a') < alphabet.code(b'b'));
+        assert!(alphabet.code(b'r') < alphabet.code(b'z'));
+        for &b in b"zebra" {
+            assert_eq!(alphabet.byte(alphabet.code(b)), b);
+        }
+    }
+
+    #[test]
+    fn test_alphabet_compaction_shrinks_index() {
+        // 数字列は 4 レイヤーで済むため、8 レイヤー相当より小さい
+        let text: Vec<u8> = (0..4096).map(|i| b'0' + (i * 7 % 10) as u8).collect();
+        let index = AliceIndex::build(&text, 32);
+        assert_eq!(index.wm.bits(), 4);
+        assert!(index.size_bytes() < text.len() * 9);
+    }
+
     // ====================================================================
     // CaseInsensitiveIndex テスト
     // ====================================================================
@@ -793,88 +1629,354 @@ mod tests {
     }
 
     #[test]
-    fn test_ascii_lower() {
-        assert_eq!(ascii_lower(b'A'), b'a');
-        assert_eq!(ascii_lower(b'Z'), b'z');
-        assert_eq!(ascii_lower(b'a'), b'a');
-        assert_eq!(ascii_lower(b'z'), b'z');
-        assert_eq!(ascii_lower(b'0'), b'0');
-        assert_eq!(ascii_lower(b' '), b' ');
-        assert_eq!(ascii_lower(0xFF), 0xFF);
+    fn test_case_insensitive_unicode_simple() {
+        let text = "Äpfel und äPFEL, ΣΟΦΙΑ σοφια".as_bytes();
+        let index = CaseInsensitiveIndex::build_with_folding(text, 1, CaseFolding::Simple);
+
+        assert_eq!(index.count("äpfel".as_bytes()), 2);
+        assert_eq!(index.count("ÄPFEL".as_bytes()), 2);
+        assert_eq!(index.count("Σοφια".as_bytes()), 2);
+        assert_eq!(index.folding(), CaseFolding::Simple);
+        assert_eq!(index.text_len(), text.len());
     }
 
-    // ====================================================================
-    // IncrementalIndex テスト
-    // ====================================================================
+    #[test]
+    fn test_case_insensitive_full_folding_offsets() {
+        // "ß" → "ss" で長さが変わっても元テキストの位置を返す
+        let text = "Straße STRASSE strasse".as_bytes();
+        let index = CaseInsensitiveIndex::build_with_folding(text, 1, CaseFolding::Full);
+
+        assert_eq!(index.count(b"strasse"), 3);
+        assert_eq!(index.count("STRAßE".as_bytes()), 3);
+        let mut positions = index.locate_all(b"strasse");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 8, 16]);
+        assert_eq!(&text[8..15], b"STRASSE");
+
+        // 展開された文字の途中から始まるマッチは文字先頭に丸める
+        let mut positions = index.locate_all(b"se");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![4, 13, 21]);
+        assert_eq!(index.text_len(), text.len());
+    }
 
     #[test]
-    fn test_incremental_empty() {
-        let index = IncrementalIndex::new(4);
-        assert!(!index.is_built());
-        assert_eq!(index.buffer_len(), 0);
-        assert_eq!(index.count(b"a"), 0);
-        assert!(!index.contains(b"a"));
-        assert!(index.locate_all(b"a").is_empty());
+    fn test_full_folding_match_inside_expansion() {
+        // "ß" → "ss": 各 "s" からのマッチを数え、位置は ß の先頭に丸める
+        let index = CaseInsensitiveIndex::build_with_folding("ß".as_bytes(), 1, CaseFolding::Full);
+        assert_eq!(index.count(b"s"), 2);
+        assert_eq!(index.locate_all(b"s"), vec![0, 0]);
+        assert_eq!(index.count(b"ss"), 1);
+        assert_eq!(index.locate_all(b"SS"), vec![0]);
+
+        // ı (U+0131) は i に折り畳まれない
+        let index = CaseInsensitiveIndex::build_with_folding("ı".as_bytes(), 1, CaseFolding::Full);
+        assert_eq!(index.count(b"i"), 0);
+        assert_eq!(index.count("ı".as_bytes()), 1);
     }
 
     #[test]
-    fn test_incremental_append_and_rebuild() {
-        let mut index = IncrementalIndex::new(4);
-        index.append(b"hello ");
-        index.append(b"world");
-        assert_eq!(index.buffer_len(), 11);
-        assert!(!index.is_built());
+    fn test_case_insensitive_dotted_i_and_kelvin() {
+        // CaseFolding.txt に従い İ と ı は i に折り畳まれない
+        let text = "İstanbul istanbul ıstanbul \u{212A}elvin";
+        let index =
+            CaseInsensitiveIndex::build_with_folding(text.as_bytes(), 2, CaseFolding::Simple);
+        assert_eq!(index.count(b"ISTANBUL"), 1);
+        assert_eq!(index.count("İSTANBUL".as_bytes()), 1);
+        assert_eq!(index.count(b"is"), 1);
+        assert_eq!(index.count(b"kelvin"), 1);
+        assert_eq!(
+            index.locate_all(b"kelvin"),
+            vec![text.find('\u{212A}').unwrap()]
+        );
+    }
 
-        index.rebuild();
-        assert!(index.is_built());
-        assert_eq!(index.count(b"hello"), 1);
-        assert!(index.contains(b"world"));
+    #[test]
+    fn test_case_insensitive_api_parity() {
+        let text = b"Hello World, HELLO world, hello";
+        let index = CaseInsensitiveIndex::build(text, 2);
+        let plain = AliceIndex::build(&text.to_ascii_lowercase(), 2);
+
+        let range = index.search_range(b"HeLLo");
+        assert_eq!(range, plain.search_range(b"hello"));
+        assert_eq!(index.locate_range(range).len(), 3);
+
+        let mut positions: Vec<_> = index.locate(b"WORLD").collect();
+        positions.sort_unstable();
+        assert_eq!(positions, vec![6, 19]);
+
+        assert_eq!(index.sample_step(), 2);
+        assert_eq!(index.size_bytes(), plain.size_bytes());
+        assert!(index.compression_ratio() > 0.0);
+        assert_eq!(index.count(b""), text.len() + 1);
     }
 
     #[test]
-    fn test_incremental_rebuild_reflects_new_data() {
-        let mut index = IncrementalIndex::new(1);
-        index.append(b"aaa");
-        index.rebuild();
-        assert_eq!(index.count(b"a"), 3);
+    fn test_case_insensitive_build_with_options() {
+        let options = IndexOptions {
+            compact_alphabet: false,
+            ..IndexOptions::with_sample_step(3)
+        };
+        let text = "Straße STRASSE".as_bytes();
+        let index = CaseInsensitiveIndex::build_with_options(text, &options, CaseFolding::Full);
+        assert_eq!(index.sample_step(), 3);
+        assert_eq!(index.inner().alphabet().sigma(), 255);
+
+        let mut positions = index.locate_all("STRASSE".as_bytes());
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 8]);
+        assert_eq!(index.count("straße".as_bytes()), 2);
+    }
 
-        index.append(b"aa");
-        // rebuild前は古いインデックス
-        assert_eq!(index.count(b"a"), 3);
+    #[test]
+    fn test_parallel_build_is_bit_identical() {
+        let text: Vec<u8> = (0..4000u32)
+            .map(|i| b"the quick brown fox; "[(i * 7 % 21) as usize] ^ (i % 3 == 0) as u8)
+            .collect();
+        let sequential = AliceIndex::build(&text, 5);
+        for threads in [0, 2, 3, 8] {
+            let options = IndexOptions {
+                threads,
+                ..IndexOptions::with_sample_step(5)
+            };
+            let parallel = AliceIndex::build_with_options(&text, &options);
+            assert!(parallel == sequential, "threads = {threads}");
+        }
+        assert_eq!(
+            sequential.locate_all(b"fox").len(),
+            sequential.count(b"fox")
+        );
+    }
 
-        index.rebuild();
-        assert_eq!(index.count(b"a"), 5);
+    #[test]
+    fn test_build_stats_reports_lean_peak() {
+        let text: Vec<u8> = (0..20_000u32)
+            .map(|i| b"ACGT"[(i * i % 7 % 4) as usize])
+            .collect();
+        let (index, stats) = AliceIndex::build_with_stats(&text, &IndexOptions::default());
+        assert!(index == AliceIndex::build(&text, 16));
+        assert_eq!(stats.sa_entry_bytes, 4);
+        assert_eq!(stats.index_bytes, index.size_bytes());
+        // 従来は u32 テキスト 4N + usize SA 8N: u32 SA・コピーなし・早期解放で 6N 未満
+        assert!(
+            stats.peak_bytes < 6 * text.len(),
+            "peak = {}",
+            stats.peak_bytes
+        );
+        assert!(stats.peak_bytes > 4 * text.len());
+    }
+
+    /// 数バイトずつしか返さないリーダー
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
     }
 
     #[test]
-    fn test_incremental_locate() {
-        let mut index = IncrementalIndex::new(1);
-        index.append(b"abcabc");
-        index.rebuild();
+    fn test_build_from_reader_matches_build() {
+        let text = b"she sells sea shells by the sea shore";
+        let options = IndexOptions::with_sample_step(4);
+        let expected = AliceIndex::build_with_options(text, &options);
+
+        let index = AliceIndex::build_from_reader(Trickle(text), &options).unwrap();
+        assert!(index == expected);
+        let hinted = AliceIndex::build_from_reader_with_hint(&text[..], 4, &options).unwrap();
+        assert!(hinted == index);
+        // 確保できないヒントは無視される
+        let hinted =
+            AliceIndex::build_from_reader_with_hint(&text[..], usize::MAX, &options).unwrap();
+        assert!(hinted == expected);
+
+        // 文書ごとのリーダーを順に連結する
+        let parts = text.split_inclusive(|&b| b == b' ').map(Trickle);
+        let index = AliceIndex::build_from_readers(parts, text.len(), &options).unwrap();
+        assert!(index == expected);
+    }
 
-        let mut positions = index.locate_all(b"abc");
-        positions.sort_unstable();
-        assert_eq!(positions, vec![0, 3]);
+    #[test]
+    fn test_build_from_reader_propagates_errors() {
+        struct Broken;
+        impl std::io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("pipe closed"))
+            }
+        }
+        let err = AliceIndex::build_from_reader(Broken, &IndexOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "pipe closed");
     }
 
     #[test]
-    fn test_incremental_clear() {
-        let mut index = IncrementalIndex::new(4);
-        index.append(b"hello");
-        index.rebuild();
-        assert!(index.is_built());
+    fn test_build_observed_reports_phases_in_order() {
+        let text = b"mississippi river banks";
+        let mut events = Vec::new();
+        let index =
+            AliceIndex::build_observed(text, &IndexOptions::default(), &mut |phase, fraction| {
+                events.push((phase, fraction));
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert!(index == AliceIndex::build(text, 16));
+
+        let first = |phase| events.iter().position(|&(p, _)| p == phase).unwrap();
+        let levels = events
+            .iter()
+            .filter_map(|&(p, _)| match p {
+                BuildPhase::SuffixArray { level } => Some(level),
+                _ => None,
+            })
+            .max()
+            .unwrap();
+        assert!(levels >= 1, "SA-IS は再帰する");
+        assert!(first(BuildPhase::SuffixArray { level: 0 }) < first(BuildPhase::Bwt));
+        assert!(first(BuildPhase::Bwt) < first(BuildPhase::Sampling));
+        let layers = index.wm.bits();
+        let last = BuildPhase::WaveletLayer {
+            layer: layers - 1,
+            layers,
+        };
+        assert_eq!(events.last(), Some(&(last, 1.0)));
+        assert!(events.iter().all(|&(_, f)| (0.0..=1.0).contains(&f)));
+    }
 
-        index.clear();
-        assert!(!index.is_built());
-        assert_eq!(index.buffer_len(), 0);
-        assert_eq!(index.count(b"hello"), 0);
+    #[test]
+    fn test_build_observed_cancels() {
+        let text = b"abracadabra";
+        let mut phases = Vec::new();
+        let full = AliceIndex::build_observed(text, &IndexOptions::default(), &mut |phase, _| {
+            phases.push(phase);
+            ControlFlow::Continue(())
+        });
+        assert!(full.is_ok());
+        for stop_at in [
+            BuildPhase::SuffixArray { level: 0 },
+            BuildPhase::Bwt,
+            BuildPhase::Sampling,
+        ] {
+            let mut seen = 0;
+            let result =
+                AliceIndex::build_observed(text, &IndexOptions::default(), &mut |phase, _| {
+                    seen += 1;
+                    if phase == stop_at {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                });
+            assert_eq!(result.err(), Some(BuildError::Cancelled));
+            // 中断を返した呼び出しが最後で、以降の通知はない
+            let first = phases.iter().position(|&p| p == stop_at).unwrap();
+            assert_eq!(seen, first + 1, "{stop_at:?}");
+        }
+        // 並列ビルドでも Wavelet 層で中断できる
+        let options = IndexOptions {
+            threads: 2,
+            ..IndexOptions::default()
+        };
+        let result = AliceIndex::build_observed(&[b'x'; 2000], &options, &mut |phase, _| {
+            if matches!(phase, BuildPhase::WaveletLayer { .. }) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(result.err(), Some(BuildError::Cancelled));
     }
 
     #[test]
-    fn test_incremental_sample_step_zero_clamped() {
-        let index = IncrementalIndex::new(0);
-        // sample_step=0 は 1 にクランプされる
-        // 正常に動作することを検証
-        assert!(!index.is_built());
+    fn test_packed_sa_samples() {
+        let text: Vec<u8> = (0..10_000u32)
+            .map(|i| b"ACGT"[(i * 7 % 13 % 4) as usize])
+            .collect();
+        let naive = |pattern: &[u8]| -> Vec<usize> {
+            (0..=text.len() - pattern.len())
+                .filter(|&i| text[i..].starts_with(pattern))
+                .collect()
+        };
+        let sparse = AliceIndex::build(&text, 1 << 20);
+        for step in [1, 3, 16] {
+            let index = AliceIndex::build(&text, step);
+            for pattern in [&b"ACG"[..], b"TTA", b"GATTACA", b"C"] {
+                let mut hits = index.locate_all(pattern);
+                hits.sort_unstable();
+                assert_eq!(hits, naive(pattern), "step {step}");
+            }
+            // 10001 行の位置は 14 ビットに詰める（usize の 8 バイトではない）
+            let samples = text.len() / step + 1;
+            assert_eq!(index.sa_samples.width(), 14);
+            assert_eq!(
+                index.size_bytes() - sparse.size_bytes(),
+                IntVector::bytes_for(samples, 14) - 8
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_without_alphabet_compaction() {
+        let text = b"abracadabra";
+        let options = IndexOptions {
+            compact_alphabet: false,
+            ..IndexOptions::with_sample_step(2)
+        };
+        let index = AliceIndex::build_with_options(text, &options);
+        let compact = AliceIndex::build(text, 2);
+        assert_eq!(index.count(b"abra"), compact.count(b"abra"));
+        assert_eq!(index.locate_all(b"cad"), vec![4]);
+        assert!(index.size_bytes() > compact.size_bytes());
+        assert_eq!(index.count(b"z"), 0);
+    }
+
+    #[test]
+    fn test_batch_queries_match_single_queries() {
+        let text: Vec<u8> = (0..20_000u32)
+            .flat_map(|i| [b"Fox ", b"dog ", b"owl ", b"Bee "][(i * 7 % 13 % 4) as usize])
+            .copied()
+            .chain("Straße STRASSE".bytes())
+            .collect();
+        // 長さ・存在・アルファベット外バイト・空パターンを混ぜる（レーン数を超える本数）
+        let mut patterns: Vec<&[u8]> = vec![b"", b"zzz", b"fox", b"\xFF", b"strasse", b"dog owl"];
+        patterns.extend((0..200).map(|k| &text[k * 97 % 19_000..][..k % 11 + 1]));
+
+        let builders = [
+            crate::build::IndexBuilder::new().sample_step(8),
+            crate::build::IndexBuilder::new()
+                .sample_step(5)
+                .sampling(SamplingStrategy::SaRank),
+            crate::build::IndexBuilder::new().case_folding(CaseFolding::Ascii),
+            crate::build::IndexBuilder::new().case_folding(CaseFolding::Full),
+            crate::build::IndexBuilder::new().separator(b' '),
+        ];
+        for builder in builders {
+            let index = builder.build(&text).unwrap();
+            let counts: Vec<usize> = patterns.iter().map(|p| index.count(p)).collect();
+            assert_eq!(index.count_batch(&patterns), counts);
+
+            // locate は出現数の少ないパターンに絞る
+            let few: Vec<&[u8]> = patterns
+                .iter()
+                .copied()
+                .filter(|p| !p.is_empty() && index.count(p) < 500)
+                .collect();
+            let located: Vec<Vec<usize>> = few.iter().map(|p| index.locate_all(p)).collect();
+            assert_eq!(index.locate_batch(&few), located);
+        }
+    }
+
+    #[test]
+    fn test_ascii_lower() {
+        assert_eq!(ascii_lower(b'A'), b'a');
+        assert_eq!(ascii_lower(b'Z'), b'z');
+        assert_eq!(ascii_lower(b'a'), b'a');
+        assert_eq!(ascii_lower(b'z'), b'z');
+        assert_eq!(ascii_lower(b'0'), b'0');
+        assert_eq!(ascii_lower(b' '), b' ');
+        assert_eq!(ascii_lower(0xFF), 0xFF);
     }
 }
@@ -0,0 +1,484 @@
+//! Word-level (Token) FM-Index
+//!
+//! **Architecture**:
+//! - `Tokenizer`: splits text into words and produces normalized keys
+//! - `Vocabulary`: maps each distinct key to an integer id (0 = sentinel)
+//! - Integer-alphabet SA-IS + `IntWaveletMatrix` over the token-id sequence
+//!
+//! A phrase query is a sequence of tokens, so `cat` never matches inside
+//! `concatenate`. Located positions are byte offsets in the original text.
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::bitvec::BitVector;
+use crate::bwt::build_suffix_array_int;
+use crate::intvec::IntVector;
+use crate::wavelet::IntWaveletMatrix;
+
+/// Token id. `0` is reserved for the sentinel; vocabulary ids start at 1.
+pub type TokenId = u32;
+
+/// テキストを単語に分割するトークナイザー。
+pub trait Tokenizer {
+    /// `text` をトークン化し、各トークンについて
+    /// (元テキスト上のバイト範囲, 正規化済みキー) を `emit` に渡す。
+    fn tokenize(&self, text: &[u8], emit: &mut dyn FnMut(Range<usize>, &[u8]));
+}
+
+/// 既定のトークナイザー。
+///
+/// ASCII 英数字と `_` の連続、および非 ASCII バイト (UTF-8 マルチバイト文字)
+/// を単語とみなし、それ以外の ASCII バイトで区切る。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WordTokenizer {
+    /// キーを ASCII 小文字に正規化するか。
+    pub lowercase: bool,
+}
+
+impl WordTokenizer {
+    /// 新規作成。
+    #[must_use]
+    pub const fn new(lowercase: bool) -> Self {
+        Self { lowercase }
+    }
+
+    #[inline]
+    const fn is_word_byte(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+    }
+}
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize(&self, text: &[u8], emit: &mut dyn FnMut(Range<usize>, &[u8])) {
+        let mut key = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            if !Self::is_word_byte(text[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < text.len() && Self::is_word_byte(text[i]) {
+                i += 1;
+            }
+            let word = &text[start..i];
+            if self.lowercase {
+                key.clear();
+                key.extend(word.iter().map(u8::to_ascii_lowercase));
+                emit(start..i, &key);
+            } else {
+                emit(start..i, word);
+            }
+        }
+    }
+}
+
+/// 単語キー ⇔ トークン ID の対応表。
+#[derive(Clone, Debug, Default)]
+pub struct Vocabulary {
+    ids: BTreeMap<Vec<u8>, TokenId>,
+    /// `words[id - 1]` = キー
+    words: Vec<Vec<u8>>,
+}
+
+impl Vocabulary {
+    /// 空の語彙を作成する。
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            ids: BTreeMap::new(),
+            words: Vec::new(),
+        }
+    }
+
+    /// キーの ID を返す。未登録なら新しい ID を割り当てる。
+    pub fn intern(&mut self, key: &[u8]) -> TokenId {
+        if let Some(&id) = self.ids.get(key) {
+            return id;
+        }
+        self.words.push(key.to_vec());
+        let id = self.words.len() as TokenId;
+        self.ids.insert(key.to_vec(), id);
+        id
+    }
+
+    /// キーの ID（未登録なら `None`）。
+    #[must_use]
+    pub fn id(&self, key: &[u8]) -> Option<TokenId> {
+        self.ids.get(key).copied()
+    }
+
+    /// ID に対応するキー。
+    #[must_use]
+    pub fn word(&self, id: TokenId) -> Option<&[u8]> {
+        let idx = (id as usize).checked_sub(1)?;
+        self.words.get(idx).map(Vec::as_slice)
+    }
+
+    /// 登録語数。
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// 語彙が空か。
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+/// 単語単位の FM-Index。
+///
+/// トークン ID 列に対して SA-IS と整数アルファベットの Wavelet Matrix を構築する。
+/// フレーズ検索はトークン列の一致であり、区切り文字の違いは無視される
+/// (`"quick, brown"` は `"quick brown"` にマッチする)。
+pub struct TokenIndex<T: Tokenizer = WordTokenizer> {
+    tokenizer: T,
+    vocab: Vocabulary,
+    /// Wavelet Matrix over the token BWT
+    wm: IntWaveletMatrix,
+    /// C-Table: `c_table[id]` = number of tokens smaller than `id`
+    c_table: Vec<usize>,
+    /// Suffix Array sampling step (in tokens)
+    sample_step: usize,
+    /// Sampled SA values (token positions, bit-packed)
+    sa_samples: IntVector,
+    /// `BitVector` marking sampled rows
+    sa_sampled_bits: BitVector,
+    /// Byte offset of each token in the original text
+    token_starts: Vec<usize>,
+    /// Original text length in bytes
+    text_len: usize,
+}
+
+impl TokenIndex<WordTokenizer> {
+    /// 既定のトークナイザー（大文字小文字を区別）でインデックスを構築する。
+    #[must_use]
+    pub fn build(text: &[u8], sample_step: usize) -> Self {
+        Self::with_tokenizer(text, sample_step, WordTokenizer::default())
+    }
+}
+
+impl<T: Tokenizer> TokenIndex<T> {
+    /// 指定したトークナイザーでインデックスを構築する。
+    #[must_use]
+    pub fn with_tokenizer(text: &[u8], sample_step: usize, tokenizer: T) -> Self {
+        let sample_step = sample_step.max(1);
+
+        // 1. Tokenize + assign ids
+        let mut vocab = Vocabulary::new();
+        let mut ids = Vec::new();
+        let mut token_starts = Vec::new();
+        tokenizer.tokenize(text, &mut |span, key| {
+            ids.push(vocab.intern(key));
+            token_starts.push(span.start);
+        });
+
+        // 2. Integer SA & BWT
+        let alpha = vocab.len() + 1;
+        let sa = build_suffix_array_int(&ids, alpha);
+        let bwt: Vec<TokenId> = sa
+            .iter()
+            .map(|&idx| if idx == 0 { 0 } else { ids[idx - 1] })
+            .collect();
+
+        let wm = IntWaveletMatrix::build(&bwt);
+
+        let mut counts = alloc::vec![0usize; alpha];
+        for &c in &bwt {
+            counts[c as usize] += 1;
+        }
+        let mut c_table = alloc::vec![0usize; alpha + 1];
+        for c in 0..alpha {
+            c_table[c + 1] = c_table[c] + counts[c];
+        }
+
+        // 3. SA samples (by token position)
+        let mut sa_samples = IntVector::new(IntVector::width_for(ids.len()));
+        let mut sa_sampled_bits = BitVector::new();
+        for &pos in &sa {
+            let sampled = pos % sample_step == 0;
+            if sampled {
+                sa_samples.push(pos);
+            }
+            sa_sampled_bits.push(sampled);
+        }
+        sa_sampled_bits.build_index();
+
+        Self {
+            tokenizer,
+            vocab,
+            wm,
+            c_table,
+            sample_step,
+            sa_samples,
+            sa_sampled_bits,
+            token_starts,
+            text_len: text.len(),
+        }
+    }
+
+    /// フレーズの出現回数。未知語を含むフレーズは 0。
+    #[must_use]
+    pub fn count(&self, phrase: &[u8]) -> usize {
+        self.phrase_ids(phrase)
+            .map_or(0, |ids| self.count_ids(&ids))
+    }
+
+    /// トークン ID 列の出現回数。O(M log σ)
+    #[must_use]
+    pub fn count_ids(&self, ids: &[TokenId]) -> usize {
+        let range = self.backward_search(ids);
+        range.end - range.start
+    }
+
+    /// フレーズが存在するか。
+    #[must_use]
+    pub fn contains(&self, phrase: &[u8]) -> bool {
+        self.count(phrase) > 0
+    }
+
+    /// フレーズの出現位置（元テキストのバイトオフセット）を返すイテレータ。
+    #[must_use]
+    pub fn locate(&self, phrase: &[u8]) -> TokenLocateIter<'_, T> {
+        let range = self
+            .phrase_ids(phrase)
+            .map_or(0..0, |ids| self.backward_search(&ids));
+        TokenLocateIter { index: self, range }
+    }
+
+    /// トークン ID 列の出現位置（バイトオフセット）を返すイテレータ。
+    #[must_use]
+    pub fn locate_ids(&self, ids: &[TokenId]) -> TokenLocateIter<'_, T> {
+        TokenLocateIter {
+            index: self,
+            range: self.backward_search(ids),
+        }
+    }
+
+    /// 全出現位置（バイトオフセット）を Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, phrase: &[u8]) -> Vec<usize> {
+        self.locate(phrase).collect()
+    }
+
+    /// 語彙。
+    #[must_use]
+    pub const fn vocabulary(&self) -> &Vocabulary {
+        &self.vocab
+    }
+
+    /// トークン数。
+    #[must_use]
+    pub fn token_len(&self) -> usize {
+        self.token_starts.len()
+    }
+
+    /// 元テキストのバイト長。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    /// SA サンプリングステップ（トークン単位）。
+    #[must_use]
+    pub const fn sample_step(&self) -> usize {
+        self.sample_step
+    }
+
+    /// インデックスの概算バイトサイズ。
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        let n = self.wm.len();
+        let usize_bytes = core::mem::size_of::<usize>();
+        let wm_size = (n / 512 + 1) * 72 * self.wm.bits();
+        let vocab_size: usize = self.vocab.words.iter().map(|w| w.len() * 2).sum();
+        wm_size
+            + vocab_size
+            + self.c_table.len() * usize_bytes
+            + (n / 512 + 1) * 72
+            + self.sa_samples.size_bytes()
+            + self.token_starts.len() * usize_bytes
+    }
+
+    /// フレーズをトークン ID 列に変換する。未知語があれば `None`。
+    fn phrase_ids(&self, phrase: &[u8]) -> Option<Vec<TokenId>> {
+        let mut ids = Vec::new();
+        let mut unknown = false;
+        self.tokenizer
+            .tokenize(phrase, &mut |_, key| match self.vocab.id(key) {
+                Some(id) => ids.push(id),
+                None => unknown = true,
+            });
+        if unknown || ids.is_empty() {
+            None
+        } else {
+            Some(ids)
+        }
+    }
+
+    /// Backward search over the token BWT
+    fn backward_search(&self, ids: &[TokenId]) -> Range<usize> {
+        if ids.is_empty() {
+            return 0..0;
+        }
+        let mut sp = 0;
+        let mut ep = self.wm.len();
+        for &c in ids.iter().rev() {
+            if c == 0 || c as usize >= self.c_table.len() - 1 {
+                return 0..0;
+            }
+            sp = self.c_table[c as usize] + self.wm.rank(c, sp);
+            ep = self.c_table[c as usize] + self.wm.rank(c, ep);
+            if sp >= ep {
+                return 0..0;
+            }
+        }
+        sp..ep
+    }
+
+    /// Resolve SA[i] (token position) via LF-mapping walk
+    fn resolve_sa(&self, mut i: usize) -> usize {
+        let mut steps = 0;
+        loop {
+            if self.sa_sampled_bits.get(i) {
+                return self.sa_samples.get(self.sa_sampled_bits.rank1(i)) + steps;
+            }
+            let c = self.wm.get(i);
+            if c == 0 {
+                return steps;
+            }
+            i = self.c_table[c as usize] + self.wm.rank(c, i);
+            steps += 1;
+        }
+    }
+}
+
+/// Iterator for token locate results (byte offsets).
+pub struct TokenLocateIter<'a, T: Tokenizer> {
+    index: &'a TokenIndex<T>,
+    range: Range<usize>,
+}
+
+impl<T: Tokenizer> Iterator for TokenLocateIter<'_, T> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.range.start >= self.range.end {
+            return None;
+        }
+        let token_pos = self.index.resolve_sa(self.range.start);
+        self.range.start += 1;
+        Some(self.index.token_starts[token_pos])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.range.end - self.range.start;
+        (len, Some(len))
+    }
+}
+
+impl<T: Tokenizer> ExactSizeIterator for TokenLocateIter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_substring_hits() {
+        // バイト単位なら "cat" は "concatenate" にもマッチするが、単語単位ではしない
+        let text = b"the cat sat; concatenate the cat";
+        let index = TokenIndex::build(text, 2);
+
+        assert_eq!(index.count(b"cat"), 2);
+        assert_eq!(index.count(b"concatenate"), 1);
+        assert_eq!(index.count(b"cate"), 0);
+    }
+
+    #[test]
+    fn test_phrase_query_and_offsets() {
+        let text = b"the quick brown fox, the quick dog";
+        let index = TokenIndex::build(text, 1);
+
+        assert_eq!(index.count(b"the quick"), 2);
+        assert_eq!(index.count(b"quick brown fox"), 1);
+        assert_eq!(index.count(b"fox the"), 1); // 区切り文字は無視される
+
+        let mut positions = index.locate_all(b"the quick");
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 21]);
+        assert_eq!(index.locate_all(b"fox"), vec![16]);
+    }
+
+    #[test]
+    fn test_unknown_word_and_empty_phrase() {
+        let index = TokenIndex::build(b"alpha beta", 4);
+        assert_eq!(index.count(b"gamma"), 0);
+        assert_eq!(index.count(b"alpha gamma"), 0);
+        assert_eq!(index.count(b""), 0);
+        assert_eq!(index.count(b" , "), 0);
+        assert!(index.locate_all(b"gamma").is_empty());
+    }
+
+    #[test]
+    fn test_lowercase_tokenizer() {
+        let index =
+            TokenIndex::with_tokenizer(b"Hello hello HELLO world", 2, WordTokenizer::new(true));
+        assert_eq!(index.count(b"hello"), 3);
+        assert_eq!(index.count(b"HeLLo World"), 1);
+        assert_eq!(index.vocabulary().len(), 2);
+    }
+
+    #[test]
+    fn test_count_locate_consistency_across_steps() {
+        let text = b"a b a b a c a b a b";
+        for step in [1, 2, 3, 8] {
+            let index = TokenIndex::build(text, step);
+            for phrase in [b"a b" as &[u8], b"b a", b"a c a", b"a b a b"] {
+                let mut positions = index.locate_all(phrase);
+                positions.sort_unstable();
+                assert_eq!(positions.len(), index.count(phrase));
+                for &p in &positions {
+                    assert_eq!(&text[p..p + phrase.len()], phrase);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_vocabulary_ids() {
+        let index = TokenIndex::build(b"x y x z", 4);
+        let vocab = index.vocabulary();
+        let x = vocab.id(b"x").unwrap();
+        assert!(x >= 1);
+        assert_eq!(vocab.word(x), Some(&b"x"[..]));
+        assert_eq!(vocab.word(0), None);
+        assert_eq!(index.count_ids(&[x]), 2);
+        assert_eq!(index.token_len(), 4);
+        assert_eq!(index.text_len(), 7);
+    }
+
+    #[test]
+    fn test_utf8_words() {
+        let text = "東京 大阪 東京".as_bytes();
+        let index = TokenIndex::build(text, 1);
+        assert_eq!(index.count("東京".as_bytes()), 2);
+        let mut positions = index.locate_all("東京".as_bytes());
+        positions.sort_unstable();
+        assert_eq!(positions, vec![0, 14]);
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let index = TokenIndex::build(b"", 4);
+        assert_eq!(index.token_len(), 0);
+        assert_eq!(index.count(b"a"), 0);
+        assert!(index.size_bytes() > 0);
+    }
+}
@@ -0,0 +1,254 @@
+//! UTF-8 Aware Search (Character Boundaries and Code-Point Offsets)
+//!
+//! **Architecture**:
+//! - `char_starts`: `BitVector` with bit `i` set iff byte `i` starts a char
+//!   (plus a final bit for the end of text)
+//! - Boundary check: two `get`s on `char_starts` per match
+//! - Byte → char offset: `rank1`; char → byte offset: `select1`
+//!
+//! When both text and pattern are valid UTF-8, every byte match is already
+//! aligned, so `count` stays a pure backward search with no filtering.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use crate::bitvec::BitVector;
+use crate::fold::decode_utf8;
+use crate::search::{AliceIndex, LocateIter};
+
+/// UTF-8 の文字境界でのみマッチを報告するインデックス。
+///
+/// 不正な UTF-8 バイトはそれぞれ 1 文字として扱う。
+pub struct Utf8Index {
+    inner: AliceIndex,
+    char_starts: BitVector,
+    valid: bool,
+}
+
+impl Utf8Index {
+    /// インデックスを構築する。
+    #[must_use]
+    pub fn build(text: &[u8], sample_step: usize) -> Self {
+        let mut char_starts = BitVector::new();
+        let mut i = 0;
+        while i < text.len() {
+            let len = decode_utf8(&text[i..]).map_or(1, |(_, len)| len);
+            char_starts.push(true);
+            for _ in 1..len {
+                char_starts.push(false);
+            }
+            i += len;
+        }
+        char_starts.push(true);
+        char_starts.build_index();
+        Self {
+            inner: AliceIndex::build(text, sample_step),
+            char_starts,
+            valid: core::str::from_utf8(text).is_ok(),
+        }
+    }
+
+    /// 文字境界に揃ったマッチの数。
+    #[must_use]
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        if self.is_aligned(pattern) {
+            let range = self.inner.search_range(pattern);
+            range.end - range.start
+        } else {
+            self.locate(pattern).count()
+        }
+    }
+
+    /// 文字境界に揃ったマッチが存在するか。
+    #[must_use]
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.locate(pattern).next().is_some()
+    }
+
+    /// 出現位置（バイトオフセット）を返すイテレータ。
+    #[must_use]
+    pub fn locate(&self, pattern: &[u8]) -> Utf8LocateIter<'_> {
+        let range = self.inner.search_range(pattern);
+        Utf8LocateIter {
+            inner: self.inner.locate_range(range),
+            char_starts: if self.is_aligned(pattern) {
+                None
+            } else {
+                Some(&self.char_starts)
+            },
+            pattern_len: pattern.len(),
+        }
+    }
+
+    /// 全出現位置（バイトオフセット）を Vec で返す。
+    #[must_use]
+    pub fn locate_all(&self, pattern: &[u8]) -> Vec<usize> {
+        self.locate(pattern).collect()
+    }
+
+    /// 全出現位置（文字オフセット）を Vec で返す。
+    #[must_use]
+    pub fn locate_chars(&self, pattern: &[u8]) -> Vec<usize> {
+        self.locate(pattern)
+            .map(|pos| self.char_starts.rank1(pos))
+            .collect()
+    }
+
+    /// バイトオフセット → 文字オフセット（`byte` より前に始まる文字の数）。O(1)
+    #[must_use]
+    pub fn byte_to_char(&self, byte: usize) -> usize {
+        self.char_starts.rank1(byte)
+    }
+
+    /// 文字オフセット → バイトオフセット。範囲外なら `None`。
+    /// `char_len()` はテキスト末尾に対応する。
+    #[must_use]
+    pub fn char_to_byte(&self, char_pos: usize) -> Option<usize> {
+        self.char_starts.select1(char_pos)
+    }
+
+    /// バイト位置が文字境界か（テキスト末尾を含む）。
+    #[must_use]
+    pub fn is_char_boundary(&self, byte: usize) -> bool {
+        byte < self.char_starts.len() && self.char_starts.get(byte)
+    }
+
+    /// テキストのバイト長。
+    #[must_use]
+    pub const fn text_len(&self) -> usize {
+        self.inner.text_len()
+    }
+
+    /// テキストの文字数。
+    #[must_use]
+    pub fn char_len(&self) -> usize {
+        self.char_starts.rank1(self.text_len())
+    }
+
+    /// テキスト全体が正しい UTF-8 か。
+    #[must_use]
+    pub const fn is_valid_utf8(&self) -> bool {
+        self.valid
+    }
+
+    /// インデックスの概算バイトサイズ（文字境界ビット列を含む）。
+    #[must_use]
+    pub const fn size_bytes(&self) -> usize {
+        self.inner.size_bytes() + self.char_starts.len().div_ceil(512) * 72
+    }
+
+    /// 内部の `AliceIndex`（バイト単位）。
+    #[must_use]
+    pub const fn inner(&self) -> &AliceIndex {
+        &self.inner
+    }
+
+    /// Valid pattern in valid text: matches cannot split a char.
+    fn is_aligned(&self, pattern: &[u8]) -> bool {
+        self.valid && core::str::from_utf8(pattern).is_ok()
+    }
+}
+
+/// Iterator for `Utf8Index` results; skips matches that split a char.
+pub struct Utf8LocateIter<'a> {
+    inner: LocateIter<'a>,
+    /// `None` when every match is known to be aligned
+    char_starts: Option<&'a BitVector>,
+    pattern_len: usize,
+}
+
+impl Iterator for Utf8LocateIter<'_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(starts) = self.char_starts else {
+            return self.inner.next();
+        };
+        self.inner
+            .by_ref()
+            .find(|&pos| starts.get(pos) && starts.get(pos + self.pattern_len))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lo, hi) = self.inner.size_hint();
+        if self.char_starts.is_some() {
+            (0, hi)
+        } else {
+            (lo, hi)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_pattern_rejected() {
+        // "é" = C3 A9, "©" = C2 A9: 継続バイトだけのパターンは境界違反
+        let text = "caf\u{e9} \u{a9}".as_bytes();
+        let index = Utf8Index::build(text, 1);
+
+        assert_eq!(index.inner().count(&[0xA9]), 2);
+        assert_eq!(index.count(&[0xA9]), 0);
+        assert!(!index.contains(&[0xA9]));
+        assert_eq!(index.count(&[0xC2, 0xA9]), 1); // "©"
+        assert_eq!(index.locate_all(&[0xC2, 0xA9]), vec![6]);
+    }
+
+    #[test]
+    fn test_prefix_fragment_rejected() {
+        // 先頭バイトのみのパターン: 開始は境界でも終了が文字の途中
+        let text = "あい".as_bytes();
+        let index = Utf8Index::build(text, 1);
+        assert_eq!(index.count(&text[..1]), 0);
+        assert_eq!(index.count(&text[..3]), 1);
+        assert!(index.locate_all(&text[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_char_offsets() {
+        let text = "日本語 text 日本".as_bytes();
+        let index = Utf8Index::build(text, 2);
+
+        assert_eq!(index.char_len(), 11);
+        assert_eq!(index.text_len(), text.len());
+
+        let mut chars = index.locate_chars("日本".as_bytes());
+        chars.sort_unstable();
+        assert_eq!(chars, vec![0, 9]);
+        assert_eq!(index.locate_chars(b"text"), vec![4]);
+
+        assert_eq!(index.char_to_byte(4), Some(10));
+        assert_eq!(index.char_to_byte(11), Some(text.len()));
+        assert_eq!(index.char_to_byte(12), None);
+        assert_eq!(index.byte_to_char(10), 4);
+        assert!(index.is_char_boundary(3));
+        assert!(!index.is_char_boundary(4));
+    }
+
+    #[test]
+    fn test_invalid_utf8_bytes() {
+        // 不正なバイトは 1 バイト 1 文字、正しい文字の途中は境界ではない
+        let mut text = vec![b'a', 0xFF, b'b', 0xA9];
+        text.extend_from_slice("é".as_bytes());
+        let index = Utf8Index::build(&text, 1);
+        assert!(!index.is_valid_utf8());
+        assert_eq!(index.char_len(), 5);
+        assert_eq!(index.count(&[0xFF, b'b']), 1);
+        assert_eq!(index.count(&[0xA9]), 1);
+        assert_eq!(index.count(&[0xA9, 0xC3]), 0);
+        assert_eq!(index.locate_chars("é".as_bytes()), vec![4]);
+    }
+
+    #[test]
+    fn test_long_text_select_across_blocks() {
+        let text = "αβγ".repeat(300);
+        let index = Utf8Index::build(text.as_bytes(), 4);
+        assert_eq!(index.char_len(), 900);
+        assert_eq!(index.char_to_byte(700), Some(1400));
+        assert_eq!(index.count("γα".as_bytes()), 299);
+    }
+}
@@ -3,51 +3,88 @@
 //! **Zero-Allocation Build**: Uses double-buffering (ping-pong) to avoid
 //! allocating vectors during construction.
 //! **Interleaved `BitVector`**: Maximizes cache hits during rank queries.
+//! **Parallel Build**: `build_parallel` splits each layer into block-aligned
+//! chunks; per-chunk zero counts fix every chunk's output slots, so the
+//! distribution runs independently and yields the sequential layout.
 //!
 //! Space: N bytes + 12.5% overhead per layer.
 
 extern crate alloc;
 use crate::bitvec::BitVector;
+use crate::build::{self, BuildError, BuildPhase, Progress};
+use crate::parallel;
 use alloc::vec;
+use alloc::vec::Vec;
 
-/// 8 layers for 8-bit characters (u8)
-const LAYERS: usize = 8;
+/// Up to 8 layers for 8-bit characters (u8)
+pub(crate) const LAYERS: usize = 8;
 
+#[derive(PartialEq, Eq)]
 pub struct WaveletMatrix {
     /// `BitVector` for each layer (interleaved layout)
     layers: [BitVector; LAYERS],
     /// Number of zeros (Z) in each layer, used for routing
     zeros: [usize; LAYERS],
+    /// Number of active layers: `⌈log₂(max_symbol + 1)⌉` (1..=8)
+    bits: usize,
     /// Length of the text
     len: usize,
+    /// Start of each symbol's run in the final (bottom) ordering,
+    /// so `get_and_rank` needs a single top-down pass
+    starts: [usize; 1 << LAYERS],
 }
 
 impl WaveletMatrix {
     /// Build Wavelet Matrix with Double Buffering (Ping-Pong)
     ///
     /// **Optimization**: Allocates only 2 auxiliary buffers of size N,
-    /// reused across all 8 layers via `mem::swap`.
+    /// reused across all layers via `mem::swap`.
     /// No intermediate allocations during layer construction.
+    ///
+    /// Only the low `⌈log₂(max_symbol + 1)⌉` layers are built, so a text
+    /// over a dense small alphabet (e.g. codes `0..=4`) needs 3 layers, not 8.
     #[must_use]
     pub fn build(text: &[u8]) -> Self {
+        build::uncancelled(Self::build_observed(text, 1, &mut Progress::none()))
+    }
+
+    /// `build` / `build_parallel` reporting every layer to `progress`.
+    pub(crate) fn build_observed(
+        text: &[u8],
+        threads: usize,
+        progress: &mut Progress<'_>,
+    ) -> Result<Self, BuildError> {
+        let threads = parallel::resolve_threads(threads);
+        if threads > 1 && !text.is_empty() {
+            return Self::build_chunked(text, threads, progress);
+        }
         let n = text.len();
         let mut layers: [BitVector; LAYERS] = core::array::from_fn(|_| BitVector::new());
         let mut zeros = [0usize; LAYERS];
 
+        let bits = Self::layer_count(text);
+
         if n == 0 {
-            return Self {
+            return Ok(Self::with_starts(Self {
                 layers,
                 zeros,
+                bits,
                 len: 0,
-            };
+                starts: [0; 1 << LAYERS],
+            }));
         }
 
         // Ping-Pong buffers: only 2 allocations for entire build
         let mut current = text.to_vec();
         let mut next = vec![0u8; n];
 
-        // Build 8 layers (MSB to LSB)
-        for d in (0..LAYERS).rev() {
+        // Build active layers (MSB to LSB)
+        for d in (0..bits).rev() {
+            let phase = BuildPhase::WaveletLayer {
+                layer: bits - 1 - d,
+                layers: bits,
+            };
+            progress.report(phase, 0.0)?;
             let layer = &mut layers[d];
             let bit_mask = 1u8 << d;
 
@@ -64,7 +101,8 @@ impl WaveletMatrix {
             let mut z_ptr = 0;
             let mut o_ptr = zero_count;
 
-            for &c in &current {
+            for (i, &c) in current.iter().enumerate() {
+                progress.tick(phase, i, n, (0.0, 1.0))?;
                 let bit = (c & bit_mask) != 0;
                 layer.push(bit);
 
@@ -78,26 +116,156 @@ impl WaveletMatrix {
             }
 
             layer.build_index();
+            progress.report(phase, 1.0)?;
 
             // Swap buffers (O(1) pointer swap, no copy)
             core::mem::swap(&mut current, &mut next);
         }
 
-        Self {
+        Ok(Self::with_starts(Self {
             layers,
             zeros,
+            bits,
             len: n,
+            starts: [0; 1 << LAYERS],
+        }))
+    }
+
+    /// Assemble from layers built elsewhere (e.g. streamed from disk).
+    /// Layers at index `bits` and above must be empty.
+    pub(crate) fn from_layers(
+        layers: [BitVector; LAYERS],
+        zeros: [usize; LAYERS],
+        bits: usize,
+        len: usize,
+    ) -> Self {
+        Self::with_starts(Self {
+            layers,
+            zeros,
+            bits,
+            len,
+            starts: [0; 1 << LAYERS],
+        })
+    }
+
+    /// Fill `starts` from finished layers: the run of `c` begins where
+    /// `rank`'s `start` cursor ends up.
+    fn with_starts(mut self) -> Self {
+        for c in 0..1usize << self.bits {
+            let mut start = 0;
+            for d in (0..self.bits).rev() {
+                start = if (c >> d) & 1 != 0 {
+                    self.zeros[d] + self.layers[d].rank1(start)
+                } else {
+                    self.layers[d].rank0(start)
+                };
+            }
+            self.starts[c] = start;
         }
+        self
+    }
+
+    /// Number of layers `build` creates for `text`: `⌈log₂(max_symbol + 1)⌉`.
+    #[must_use]
+    pub fn layer_count(text: &[u8]) -> usize {
+        let max = text.iter().copied().max().unwrap_or(0);
+        (u8::BITS - max.leading_zeros()).max(1) as usize
+    }
+
+    /// Build on `threads` scoped threads (`0` = all cores).
+    ///
+    /// Bit-identical to [`WaveletMatrix::build`]. Each layer makes two
+    /// parallel passes: zero counting per chunk, then bit packing and
+    /// distribution into disjoint slots of the next buffer.
+    #[must_use]
+    pub fn build_parallel(text: &[u8], threads: usize) -> Self {
+        build::uncancelled(Self::build_observed(text, threads, &mut Progress::none()))
+    }
+
+    fn build_chunked(
+        text: &[u8],
+        threads: usize,
+        progress: &mut Progress<'_>,
+    ) -> Result<Self, BuildError> {
+        let n = text.len();
+
+        let bits = Self::layer_count(text);
+        let mut layers: [BitVector; LAYERS] = core::array::from_fn(|_| BitVector::new());
+        let mut zeros = [0usize; LAYERS];
+
+        // Chunk boundaries on 512-bit blocks: packed words concatenate exactly
+        let ranges = parallel::chunk_ranges(n, threads, 512);
+        let mut current = text.to_vec();
+        let mut next = vec![0u8; n];
+
+        for d in (0..bits).rev() {
+            let phase = BuildPhase::WaveletLayer {
+                layer: bits - 1 - d,
+                layers: bits,
+            };
+            progress.report(phase, 0.0)?;
+            let bit_mask = 1u8 << d;
+
+            // Pass 1: zero count per chunk
+            let chunk_zeros = parallel::scope_map(ranges.clone(), |r| {
+                current[r].iter().filter(|&&c| c & bit_mask == 0).count()
+            });
+            let zero_count: usize = chunk_zeros.iter().sum();
+            zeros[d] = zero_count;
+
+            // Pass 2: each chunk owns one slice of the zero part and one of the one part
+            let (zero_part, one_part) = next.split_at_mut(zero_count);
+            let zero_dst = parallel::split_lengths(zero_part, chunk_zeros.iter().copied());
+            let one_dst = parallel::split_lengths(
+                one_part,
+                ranges.iter().zip(&chunk_zeros).map(|(r, &z)| r.len() - z),
+            );
+            let jobs: Vec<_> = ranges
+                .iter()
+                .cloned()
+                .zip(zero_dst)
+                .zip(one_dst)
+                .map(|((r, z), o)| (r, z, o))
+                .collect();
+            let words = parallel::scope_map(jobs, |(r, zero_dst, one_dst)| {
+                let src = &current[r];
+                let mut words = vec![0u64; src.len().div_ceil(64)];
+                let (mut z_ptr, mut o_ptr) = (0, 0);
+                for (i, &c) in src.iter().enumerate() {
+                    if c & bit_mask != 0 {
+                        words[i / 64] |= 1 << (i % 64);
+                        one_dst[o_ptr] = c;
+                        o_ptr += 1;
+                    } else {
+                        zero_dst[z_ptr] = c;
+                        z_ptr += 1;
+                    }
+                }
+                words
+            });
+            layers[d] = BitVector::from_words(&words.concat(), n);
+            progress.report(phase, 1.0)?;
+
+            core::mem::swap(&mut current, &mut next);
+        }
+
+        Ok(Self::with_starts(Self {
+            layers,
+            zeros,
+            bits,
+            len: n,
+            starts: [0; 1 << LAYERS],
+        }))
     }
 
     /// Get character at position i
-    /// O(8) operations - fixed cost regardless of alphabet size
+    /// O(bits) ≤ 8 operations - fixed cost regardless of text size
     #[inline]
     #[must_use]
     pub fn get(&self, mut i: usize) -> u8 {
         let mut c = 0u8;
 
-        for d in (0..LAYERS).rev() {
+        for d in (0..self.bits).rev() {
             let bit = self.layers[d].get(i);
             c |= (bit as u8) << d;
 
@@ -111,13 +279,16 @@ impl WaveletMatrix {
     }
 
     /// Rank(c, i): Count occurrences of character c in [0..i)
-    /// O(8) operations - independent of text size
+    /// O(bits) ≤ 8 operations - independent of text size
     #[inline]
     #[must_use]
     pub fn rank(&self, c: u8, mut i: usize) -> usize {
+        if (c as usize) >> self.bits != 0 {
+            return 0; // Character wider than the active layers: never present
+        }
         let mut start = 0;
 
-        for d in (0..LAYERS).rev() {
+        for d in (0..self.bits).rev() {
             let bit = (c >> d) & 1 != 0;
 
             let rank_start = self.layers[d].rank(bit, start);
@@ -135,6 +306,277 @@ impl WaveletMatrix {
         i - start
     }
 
+    /// (Rank(c, sp), Rank(c, ep)) in one traversal, for `sp <= ep`
+    ///
+    /// The two-sided rank of a backward-search step: both cursors descend
+    /// the same path, so each layer's `BitVector` is visited once for the
+    /// pair (`rank1_pair` shares the block header when they are close), and
+    /// the run start comes from the `starts` table instead of a third cursor.
+    /// O(bits) ≤ 8 operations
+    #[inline]
+    #[must_use]
+    pub fn rank_pair(&self, c: u8, mut sp: usize, mut ep: usize) -> (usize, usize) {
+        if (c as usize) >> self.bits != 0 {
+            return (0, 0); // Character wider than the active layers: never present
+        }
+
+        for d in (0..self.bits).rev() {
+            let (ones_sp, ones_ep) = self.layers[d].rank1_pair(sp, ep);
+
+            if (c >> d) & 1 != 0 {
+                sp = self.zeros[d] + ones_sp;
+                ep = self.zeros[d] + ones_ep;
+            } else {
+                sp -= ones_sp;
+                ep -= ones_ep;
+            }
+        }
+
+        let start = self.starts[c as usize];
+        (sp - start, ep - start)
+    }
+
+    /// Batched `rank_pair`: `out[k] = rank_pair(c, sp, ep)` for `queries[k]`
+    ///
+    /// All queries descend the layers in lockstep. Once a query has moved
+    /// down a layer, the block it needs on the next one is prefetched, so
+    /// its cache misses overlap the rest of the batch instead of stalling a
+    /// single dependent chain. A few dozen queries keep enough misses in
+    /// flight; more only add working set.
+    ///
+    /// # Panics
+    /// Panics if `queries` and `out` differ in length.
+    pub fn rank_pair_batch(&self, queries: &[(u8, usize, usize)], out: &mut [(usize, usize)]) {
+        assert_eq!(queries.len(), out.len(), "rank_pair_batch: length mismatch");
+        let top = self.bits.checked_sub(1);
+
+        for (o, &(_, sp, ep)) in out.iter_mut().zip(queries) {
+            *o = (sp, ep);
+            if let Some(top) = top {
+                self.layers[top].prefetch(sp);
+                self.layers[top].prefetch(ep);
+            }
+        }
+
+        for d in (0..self.bits).rev() {
+            let layer = &self.layers[d];
+            for (o, &(c, ..)) in out.iter_mut().zip(queries) {
+                if (c as usize) >> self.bits != 0 {
+                    continue; // Never present: settled below
+                }
+                let (ones_sp, ones_ep) = layer.rank1_pair(o.0, o.1);
+                *o = if (c >> d) & 1 != 0 {
+                    (self.zeros[d] + ones_sp, self.zeros[d] + ones_ep)
+                } else {
+                    (o.0 - ones_sp, o.1 - ones_ep)
+                };
+                if d > 0 {
+                    self.layers[d - 1].prefetch(o.0);
+                    self.layers[d - 1].prefetch(o.1);
+                }
+            }
+        }
+
+        for (o, &(c, ..)) in out.iter_mut().zip(queries) {
+            *o = if (c as usize) >> self.bits != 0 {
+                (0, 0)
+            } else {
+                let start = self.starts[c as usize];
+                (o.0 - start, o.1 - start)
+            };
+        }
+    }
+
+    /// Batched `get_and_rank`: `out[k] = get_and_rank(rows[k])`
+    ///
+    /// Lockstep descent with prefetch, as in `rank_pair_batch`.
+    ///
+    /// # Panics
+    /// Panics if `rows` and `out` differ in length.
+    pub fn get_and_rank_batch(&self, rows: &[usize], out: &mut [(u8, usize)]) {
+        assert_eq!(rows.len(), out.len(), "get_and_rank_batch: length mismatch");
+
+        for (o, &i) in out.iter_mut().zip(rows) {
+            *o = (0, i);
+            if let Some(top) = self.bits.checked_sub(1) {
+                self.layers[top].prefetch(i);
+            }
+        }
+
+        for d in (0..self.bits).rev() {
+            let layer = &self.layers[d];
+            for o in out.iter_mut() {
+                let (c, i) = *o;
+                let bit = layer.get(i);
+                let i = if bit {
+                    self.zeros[d] + layer.rank1(i)
+                } else {
+                    layer.rank0(i)
+                };
+                *o = (c | (bit as u8) << d, i);
+                if d > 0 {
+                    self.layers[d - 1].prefetch(i);
+                }
+            }
+        }
+
+        for o in out.iter_mut() {
+            o.1 -= self.starts[o.0 as usize];
+        }
+    }
+
+    /// Fused access + rank: `(get(i), rank(get(i), i))` in one top-down pass
+    ///
+    /// The LF step of locate / extract needs both; `get` already tracks
+    /// `i` down to the bottom ordering, where each symbol occupies one run,
+    /// so the rank is that position minus the run start.
+    /// O(bits) ≤ 8 operations - half the work of `get` followed by `rank`
+    #[inline]
+    #[must_use]
+    pub fn get_and_rank(&self, mut i: usize) -> (u8, usize) {
+        let mut c = 0u8;
+
+        for d in (0..self.bits).rev() {
+            let bit = self.layers[d].get(i);
+            c |= (bit as u8) << d;
+
+            i = if bit {
+                self.zeros[d] + self.layers[d].rank1(i)
+            } else {
+                self.layers[d].rank0(i)
+            };
+        }
+        (c, i - self.starts[c as usize])
+    }
+
+    /// Number of active bit layers
+    #[inline]
+    #[must_use]
+    pub const fn bits(&self) -> usize {
+        self.bits
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Wavelet Matrix over an integer alphabet (`u32` symbols).
+///
+/// Same layout as [`WaveletMatrix`], but the number of layers is
+/// `⌈log₂(max_symbol + 1)⌉` instead of a fixed 8. Used for token sequences
+/// where the alphabet is a vocabulary rather than bytes.
+pub struct IntWaveletMatrix {
+    /// `BitVector` for each layer, index = bit position
+    layers: Vec<BitVector>,
+    /// Number of zeros (Z) in each layer
+    zeros: Vec<usize>,
+    /// Length of the sequence
+    len: usize,
+}
+
+impl IntWaveletMatrix {
+    /// Build with double buffering (same scheme as [`WaveletMatrix::build`]).
+    #[must_use]
+    pub fn build(text: &[u32]) -> Self {
+        let n = text.len();
+        let max = text.iter().copied().max().unwrap_or(0);
+        let bits = (u32::BITS - max.leading_zeros()).max(1) as usize;
+
+        let mut layers: Vec<BitVector> = (0..bits).map(|_| BitVector::new()).collect();
+        let mut zeros = vec![0usize; bits];
+
+        let mut current = text.to_vec();
+        let mut next = vec![0u32; n];
+
+        for d in (0..bits).rev() {
+            let layer = &mut layers[d];
+
+            let zero_count = current.iter().filter(|&&c| (c >> d) & 1 == 0).count();
+            zeros[d] = zero_count;
+
+            let mut z_ptr = 0;
+            let mut o_ptr = zero_count;
+            for &c in &current {
+                let bit = (c >> d) & 1 != 0;
+                layer.push(bit);
+                if bit {
+                    next[o_ptr] = c;
+                    o_ptr += 1;
+                } else {
+                    next[z_ptr] = c;
+                    z_ptr += 1;
+                }
+            }
+            layer.build_index();
+
+            core::mem::swap(&mut current, &mut next);
+        }
+
+        Self {
+            layers,
+            zeros,
+            len: n,
+        }
+    }
+
+    /// Get symbol at position i. O(log σ)
+    #[inline]
+    #[must_use]
+    pub fn get(&self, mut i: usize) -> u32 {
+        let mut c = 0u32;
+        for d in (0..self.layers.len()).rev() {
+            let layer = &self.layers[d];
+            let bit = layer.get(i);
+            c |= (bit as u32) << d;
+            i = if bit {
+                self.zeros[d] + layer.rank1(i)
+            } else {
+                layer.rank0(i)
+            };
+        }
+        c
+    }
+
+    /// Rank(c, i): Count occurrences of symbol c in [0..i). O(log σ)
+    #[inline]
+    #[must_use]
+    pub fn rank(&self, c: u32, mut i: usize) -> usize {
+        if (c as u64) >> self.layers.len() != 0 {
+            return 0; // Symbol wider than the matrix: never present
+        }
+        let mut start = 0;
+        for d in (0..self.layers.len()).rev() {
+            let layer = &self.layers[d];
+            let bit = (c >> d) & 1 != 0;
+            let rank_start = layer.rank(bit, start);
+            let rank_end = layer.rank(bit, i);
+            if bit {
+                start = self.zeros[d] + rank_start;
+                i = self.zeros[d] + rank_end;
+            } else {
+                start = rank_start;
+                i = rank_end;
+            }
+        }
+        i - start
+    }
+
+    /// Number of bit layers (= bits per symbol)
+    #[inline]
+    #[must_use]
+    pub fn bits(&self) -> usize {
+        self.layers.len()
+    }
+
     #[inline]
     #[must_use]
     pub const fn len(&self) -> usize {
@@ -152,6 +594,79 @@ impl WaveletMatrix {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parallel_build_is_bit_identical() {
+        let text: Vec<u8> = (0..5000u32)
+            .map(|i| ((i * 31 % 97) ^ (i / 13)) as u8)
+            .collect();
+        for threads in [2, 3, 8] {
+            assert!(WaveletMatrix::build_parallel(&text, threads) == WaveletMatrix::build(&text));
+        }
+        assert!(WaveletMatrix::build_parallel(b"ab", 4) == WaveletMatrix::build(b"ab"));
+        assert!(WaveletMatrix::build_parallel(b"", 4) == WaveletMatrix::build(b""));
+    }
+
+    #[test]
+    fn test_rank_pair_matches_separate_calls() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| ((i * 131 % 251) ^ (i / 7)) as u8 % 40)
+            .collect();
+        let wm = WaveletMatrix::build(&text);
+        for c in [0u8, 1, 17, 39, 40, 200] {
+            for sp in (0..=text.len()).step_by(97) {
+                for ep in [sp, sp + 1, sp + 60, sp + 700, text.len()] {
+                    let ep = ep.min(text.len());
+                    assert_eq!(
+                        wm.rank_pair(c, sp, ep),
+                        (wm.rank(c, sp), wm.rank(c, ep)),
+                        "文字 {c}, 区間 {sp}..{ep}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_batches_match_single_queries() {
+        let text: Vec<u8> = (0..5000u32)
+            .map(|i| ((i * 131 % 251) ^ (i / 7)) as u8 % 90)
+            .collect();
+        let wm = WaveletMatrix::build(&text);
+
+        let queries: Vec<(u8, usize, usize)> = (0..300usize)
+            .map(|k| {
+                let sp = k * 7919 % text.len();
+                ((k * 37 % 140) as u8, sp, (sp + k * 13).min(text.len()))
+            })
+            .collect();
+        let mut out = vec![(0, 0); queries.len()];
+        wm.rank_pair_batch(&queries, &mut out);
+        for (&(c, sp, ep), &got) in queries.iter().zip(&out) {
+            assert_eq!(got, wm.rank_pair(c, sp, ep), "文字 {c}, 区間 {sp}..{ep}");
+        }
+
+        let rows: Vec<usize> = (0..300).map(|k| k * 7919 % text.len()).collect();
+        let mut out = vec![(0, 0); rows.len()];
+        wm.get_and_rank_batch(&rows, &mut out);
+        for (&i, &got) in rows.iter().zip(&out) {
+            assert_eq!(got, wm.get_and_rank(i), "位置 {i}");
+        }
+    }
+
+    #[test]
+    fn test_get_and_rank_matches_separate_calls() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| ((i * 131 % 251) ^ (i / 7)) as u8)
+            .collect();
+        for text in [&text[..], b"abracadabra", b"\0", b""] {
+            let wm = WaveletMatrix::build(text);
+            for i in 0..text.len() {
+                let c = wm.get(i);
+                assert_eq!(wm.get_and_rank(i), (c, wm.rank(c, i)), "位置 {i}");
+            }
+        }
+    }
+
     #[test]
     fn test_wavelet_get() {
         let text = b"abracadabra";
@@ -312,4 +827,54 @@ mod tests {
         assert_eq!(wm.rank(b's', n), 4);
         assert_eq!(wm.rank(b'p', n), 2);
     }
+
+    #[test]
+    fn test_wavelet_layer_count_follows_alphabet() {
+        // 記号 0..=4 は 3 レイヤーで表現できる
+        let text: Vec<u8> = vec![1, 2, 3, 4, 0, 4, 3];
+        let wm = WaveletMatrix::build(&text);
+        assert_eq!(wm.bits(), 3);
+        for (i, &c) in text.iter().enumerate() {
+            assert_eq!(wm.get(i), c);
+        }
+        assert_eq!(wm.rank(4, 7), 2);
+        assert_eq!(wm.rank(8, 7), 0); // 3 ビットに収まらない記号
+        assert_eq!(wm.rank(0xFF, 7), 0);
+
+        assert_eq!(WaveletMatrix::build(&[0xFF]).bits(), 8);
+        assert_eq!(WaveletMatrix::build(&[0, 0]).bits(), 1);
+    }
+
+    #[test]
+    fn test_int_wavelet_get_and_rank() {
+        let text: Vec<u32> = vec![5, 1000, 5, 3, 70_000, 1000, 5];
+        let wm = IntWaveletMatrix::build(&text);
+
+        assert_eq!(wm.len(), text.len());
+        assert_eq!(wm.bits(), 17); // 70_000 < 2^17
+        for (i, &c) in text.iter().enumerate() {
+            assert_eq!(wm.get(i), c, "get({i}) mismatch");
+        }
+        assert_eq!(wm.rank(5, 7), 3);
+        assert_eq!(wm.rank(1000, 2), 1);
+        assert_eq!(wm.rank(70_000, 4), 0);
+        assert_eq!(wm.rank(70_000, 5), 1);
+        assert_eq!(wm.rank(4, 7), 0);
+    }
+
+    #[test]
+    fn test_int_wavelet_symbol_wider_than_matrix() {
+        // 行列の幅を超える記号は rank 0
+        let wm = IntWaveletMatrix::build(&[1, 2, 3]);
+        assert_eq!(wm.bits(), 2);
+        assert_eq!(wm.rank(4, 3), 0);
+        assert_eq!(wm.rank(u32::MAX, 3), 0);
+    }
+
+    #[test]
+    fn test_int_wavelet_empty() {
+        let wm = IntWaveletMatrix::build(&[]);
+        assert!(wm.is_empty());
+        assert_eq!(wm.rank(1, 0), 0);
+    }
 }